@@ -0,0 +1,275 @@
+use crate::ai::expectimax::heuristic::{Evaluator, Heuristic, HeuristicWeights, LineTable};
+use crate::ai::{MoveScores, ScoreMoves, AI};
+use crate::engine;
+use crate::engine::{Board, GameEngine, Move, Score};
+use rand::rngs::ThreadRng;
+use rand::thread_rng;
+
+// Adversarial game mode: instead of spawning tiles at random a spawner picks where the new tile
+// goes and whether it is a 2 or a 4. The minimax spawner plays the worst placement for the player
+// it can find, the minimax agent plays the player side assuming the spawner does exactly that.
+
+pub trait Spawner {
+    // Adds a tile to the board, the board must have at least one empty tile
+    fn spawn<T: GameEngine>(&mut self, engine: &T, board: Board) -> Board;
+}
+
+// The normal game, a 2 (90%) or 4 (10%) in a random empty tile
+pub struct RandomSpawner {
+    rng: ThreadRng,
+}
+
+impl RandomSpawner {
+    pub fn new() -> Self {
+        RandomSpawner { rng: thread_rng() }
+    }
+}
+
+impl Default for RandomSpawner {
+    fn default() -> Self {
+        RandomSpawner::new()
+    }
+}
+
+impl Spawner for RandomSpawner {
+    fn spawn<T: GameEngine>(&mut self, _engine: &T, board: Board) -> Board {
+        engine::insert_random_tile_with_rng(board, &mut self.rng)
+    }
+}
+
+// Looks depth player moves ahead and picks the spawn that minimises the evaluation
+pub struct MinimaxSpawner<E: Evaluator = LineTable> {
+    depth: u64,
+    evaluator: E,
+}
+
+impl MinimaxSpawner {
+    pub fn new(depth: u64) -> Self {
+        MinimaxSpawner::with_evaluator(depth, HeuristicWeights::default().build_table())
+    }
+}
+
+impl<E: Evaluator> MinimaxSpawner<E> {
+    pub fn with_evaluator(depth: u64, evaluator: E) -> Self {
+        MinimaxSpawner { depth, evaluator }
+    }
+}
+
+impl<E: Evaluator> Spawner for MinimaxSpawner<E> {
+    fn spawn<T: GameEngine>(&mut self, engine: &T, board: Board) -> Board {
+        evaluate_spawner(
+            engine,
+            &self.evaluator,
+            board,
+            self.depth,
+            f64::NEG_INFINITY,
+            f64::INFINITY,
+        )
+        .1
+        .expect("The spawner needs an empty tile")
+    }
+}
+
+// Player agent that assumes the worst spawn is always played, searching depth player moves ahead
+// with alpha-beta pruning
+pub struct Minimax<E: Evaluator = LineTable> {
+    depth: u64,
+    evaluator: E,
+}
+
+impl Minimax {
+    pub fn new(depth: u64) -> Self {
+        Minimax::with_evaluator(depth, HeuristicWeights::default().build_table())
+    }
+}
+
+impl<E: Evaluator> Minimax<E> {
+    pub fn with_evaluator(depth: u64, evaluator: E) -> Self {
+        Minimax { depth, evaluator }
+    }
+}
+
+impl<E: Evaluator> AI for Minimax<E> {
+    fn get_next_move<T: GameEngine>(&mut self, engine: &T, board: Board) -> Option<Move> {
+        evaluate_player(
+            engine,
+            &self.evaluator,
+            board,
+            self.depth.max(1),
+            f64::NEG_INFINITY,
+            f64::INFINITY,
+        )
+        .1
+    }
+}
+
+impl<E: Evaluator> ScoreMoves for Minimax<E> {
+    // The minimax value of each possible move. Every move is searched with the full window so the
+    // values are exact, where get_next_move only bounds the moves alpha-beta prunes.
+    fn score_moves<T: GameEngine>(&mut self, engine: &T, board: Board) -> MoveScores {
+        let mut move_scores = MoveScores::new();
+        for direction in Move::iterator() {
+            let new_board = engine.shift(board, direction);
+            if new_board == board {
+                continue;
+            }
+            let score = if self.depth <= 1 {
+                self.evaluator.evaluate(new_board)
+            } else {
+                evaluate_spawner(
+                    engine,
+                    &self.evaluator,
+                    new_board,
+                    self.depth - 1,
+                    f64::NEG_INFINITY,
+                    f64::INFINITY,
+                )
+                .0
+            };
+            move_scores.insert(direction, score);
+        }
+        move_scores
+    }
+}
+
+// Returns the value of the board with the player to move and the best move, a lost board is 0
+fn evaluate_player<T: GameEngine, E: Evaluator>(
+    engine: &T,
+    evaluator: &E,
+    board: Board,
+    depth: u64,
+    mut alpha: f64,
+    beta: f64,
+) -> (f64, Option<Move>) {
+    let mut best_score = 0.;
+    let mut best_move = None;
+    for direction in Move::iterator() {
+        let new_board = engine.shift(board, direction);
+        if new_board == board {
+            continue;
+        }
+        let score = if depth <= 1 {
+            evaluator.evaluate(new_board)
+        } else {
+            evaluate_spawner(engine, evaluator, new_board, depth - 1, alpha, beta).0
+        };
+        if best_move.is_none() || score > best_score {
+            best_score = score;
+            best_move = Some(direction);
+        }
+        alpha = alpha.max(score);
+        if alpha >= beta {
+            break;
+        }
+    }
+    (best_score, best_move)
+}
+
+// Returns the value of the board with the spawner to move and the worst spawn for the player
+fn evaluate_spawner<T: GameEngine, E: Evaluator>(
+    engine: &T,
+    evaluator: &E,
+    board: Board,
+    depth: u64,
+    alpha: f64,
+    mut beta: f64,
+) -> (f64, Option<Board>) {
+    let mut worst_score = f64::INFINITY;
+    let mut worst_board = None;
+    for new_board in get_spawns(board) {
+        let score = evaluate_player(engine, evaluator, new_board, depth, alpha, beta).0;
+        if score < worst_score {
+            worst_score = score;
+            worst_board = Some(new_board);
+        }
+        beta = beta.min(score);
+        if alpha >= beta {
+            break;
+        }
+    }
+    (worst_score, worst_board)
+}
+
+// Every board that can be made by adding a 2 or 4 to an empty tile
+fn get_spawns(board: Board) -> Vec<Board> {
+    engine::get_spawns(board)
+        .into_iter()
+        .map(|(new_board, _)| new_board)
+        .collect()
+}
+
+// Plays a game where the spawner places every tile, including the two starting tiles
+pub fn play_adversarial<A: AI, S: Spawner, T: GameEngine>(
+    ai: &mut A,
+    spawner: &mut S,
+    engine: &T,
+) -> Board {
+    let mut board = spawner.spawn(engine, 0);
+    board = spawner.spawn(engine, board);
+    while let Some(direction) = ai.get_next_move(engine, board) {
+        let new_board = engine.shift(board, direction);
+        if new_board != board {
+            board = spawner.spawn(engine, new_board);
+        }
+    }
+    board
+}
+
+pub fn run_adversarial<A: AI, S: Spawner, T: GameEngine>(
+    ai: &mut A,
+    spawner: &mut S,
+    engine: &T,
+    runs: usize,
+) -> Vec<Score> {
+    (0..runs)
+        .map(|_| engine.get_score(play_adversarial(ai, spawner, engine)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::GameEngineNoStores;
+
+    #[test]
+    fn it_get_spawns() {
+        let spawns = get_spawns(0x1234432112344320);
+        assert_eq!(spawns, vec![0x1234432112344321, 0x1234432112344322]);
+    }
+
+    #[test]
+    fn it_minimax_spawner_worst_placement() {
+        let engine = GameEngineNoStores;
+        // a 4 in the last empty tile is the only spawn that loses the game
+        let board = 0x1234432112344310;
+        assert_eq!(
+            MinimaxSpawner::new(1).spawn(&engine, board),
+            0x1234432112344312
+        );
+    }
+
+    #[test]
+    fn it_minimax_agent() {
+        let engine = GameEngineNoStores;
+        let mut minimax = Minimax::new(2);
+        assert_eq!(
+            minimax.get_next_move(&engine, 0x0000000000001234),
+            Some(Move::Up)
+        );
+        assert_eq!(minimax.get_next_move(&engine, 0x1234432112344321), None);
+        let board = 0x0000012000300000;
+        assert_eq!(
+            minimax.score_moves(&engine, board).best(),
+            minimax.get_next_move(&engine, board)
+        );
+        assert_eq!(
+            minimax
+                .score_moves(&engine, 0x0000000000001234)
+                .get_scores()
+                .len(),
+            1
+        );
+        let scores = run_adversarial(&mut minimax, &mut MinimaxSpawner::new(1), &engine, 1);
+        assert_eq!(scores.len(), 1);
+    }
+}
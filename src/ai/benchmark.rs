@@ -0,0 +1,233 @@
+use crate::ai::{MoveScores, ScoreMoves, AI};
+use crate::engine;
+use crate::engine::{Board, GameEngine, Move};
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::Path;
+
+// A suite of positions sampled from played games, each labelled by a strong agent (normally a
+// deep expectimax search) with its best move and the gap between the value of the best move and
+// every other possible move. Any agent can then be scored on how often it agrees with the label
+// and how much value its moves lose, without playing full games.
+//
+// The suite is saved as csv: board,best_move,up_gap,down_gap,left_gap,right_gap, the board is in
+// hex and the gap of an impossible move is left empty.
+
+const DIRECTIONS: [Move; 4] = [Move::Up, Move::Down, Move::Left, Move::Right];
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct BenchmarkPosition {
+    pub board: Board,
+    pub best_move: Move,
+    // best value - value of the move, 0 for the best move
+    pub gaps: MoveScores,
+}
+
+impl BenchmarkPosition {
+    // None if the labeller has no move for the board
+    pub fn label<V: ScoreMoves, E: GameEngine>(
+        labeller: &mut V,
+        engine: &E,
+        board: Board,
+    ) -> Option<Self> {
+        let move_scores = labeller.score_moves(engine, board);
+        let best_move = move_scores.best()?;
+        let best_value = move_scores.get(best_move)?;
+        let mut gaps = MoveScores::new();
+        for &(direction, value) in move_scores.get_scores() {
+            gaps.insert(direction, best_value - value);
+        }
+        Some(BenchmarkPosition {
+            board,
+            best_move,
+            gaps,
+        })
+    }
+}
+
+// The player plays games until enough positions are collected, a position is labelled every
+// sample_every moves
+pub fn generate_positions<A: AI, V: ScoreMoves, E: GameEngine>(
+    player: &mut A,
+    labeller: &mut V,
+    engine: &E,
+    num_positions: usize,
+    sample_every: usize,
+) -> Vec<BenchmarkPosition> {
+    assert!(sample_every > 0, "sample_every must be at least 1");
+    let mut positions = Vec::new();
+    while positions.len() < num_positions {
+        let mut board = engine::new_board();
+        let mut num_moves = 0;
+        while let Some(direction) = player.get_next_move(engine, board) {
+            if num_moves % sample_every == 0 {
+                if let Some(position) = BenchmarkPosition::label(labeller, engine, board) {
+                    positions.push(position);
+                    if positions.len() % 100 == 0 {
+                        println!("Labelled {}/{} positions", positions.len(), num_positions);
+                    }
+                    if positions.len() == num_positions {
+                        break;
+                    }
+                }
+            }
+            board = engine.make_move(board, direction);
+            num_moves += 1;
+        }
+    }
+    positions
+}
+
+pub fn save_positions(positions: &[BenchmarkPosition], filename: &str) -> io::Result<()> {
+    let mut f = File::create(Path::new(filename))?;
+    f.write_all("board,best_move,up_gap,down_gap,left_gap,right_gap\n".as_bytes())?;
+    for position in positions {
+        let gaps = DIRECTIONS
+            .iter()
+            .map(|&direction| match position.gaps.get(direction) {
+                Some(gap) => gap.to_string(),
+                None => String::new(),
+            })
+            .collect::<Vec<_>>();
+        f.write_fmt(format_args!(
+            "{:016x},{},{}\n",
+            position.board,
+            position.best_move,
+            gaps.join(",")
+        ))?;
+    }
+    Ok(())
+}
+
+pub fn load_positions(filename: &str) -> io::Result<Vec<BenchmarkPosition>> {
+    let f = File::open(Path::new(filename))?;
+    let mut positions = Vec::new();
+    for line in BufReader::new(f).lines().skip(1) {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let invalid_line = || {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("invalid benchmark position: {}", line),
+            )
+        };
+        let fields = line
+            .split(',')
+            .map(|field| field.trim())
+            .collect::<Vec<_>>();
+        if fields.len() != 6 {
+            return Err(invalid_line());
+        }
+        let board = u64::from_str_radix(fields[0], 16).map_err(|_| invalid_line())?;
+        let best_move = DIRECTIONS
+            .iter()
+            .cloned()
+            .find(|direction| direction.to_string() == fields[1])
+            .ok_or_else(invalid_line)?;
+        let mut gaps = MoveScores::new();
+        for (&direction, gap) in DIRECTIONS.iter().zip(&fields[2..]) {
+            if !gap.is_empty() {
+                gaps.insert(direction, gap.parse::<f64>().map_err(|_| invalid_line())?);
+            }
+        }
+        positions.push(BenchmarkPosition {
+            board,
+            best_move,
+            gaps,
+        });
+    }
+    Ok(positions)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BenchmarkScore {
+    pub positions: usize,
+    // fraction of positions where the agent played the labelled best move
+    pub agreement: f64,
+    // average value lost against the best move
+    pub average_regret: f64,
+}
+
+impl fmt::Display for BenchmarkScore {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Positions: {}, top-1 agreement: {:.1}%, average regret: {:.2}",
+            self.positions,
+            self.agreement * 100.,
+            self.average_regret
+        )
+    }
+}
+
+// A move that the labeller did not consider possible (or no move at all) has the regret of the
+// worst possible move. None if there are no positions to score.
+pub fn score_agent<A: AI, E: GameEngine>(
+    agent: &mut A,
+    engine: &E,
+    positions: &[BenchmarkPosition],
+) -> Option<BenchmarkScore> {
+    if positions.is_empty() {
+        return None;
+    }
+    let mut agreed = 0;
+    let mut total_regret = 0.;
+    for position in positions {
+        let chosen_move = agent.get_next_move(engine, position.board);
+        if chosen_move == Some(position.best_move) {
+            agreed += 1;
+        }
+        let worst_gap = position
+            .gaps
+            .get_scores()
+            .iter()
+            .map(|&(_, gap)| gap)
+            .fold(0., f64::max);
+        total_regret += chosen_move
+            .and_then(|direction| position.gaps.get(direction))
+            .unwrap_or(worst_gap);
+    }
+    Some(BenchmarkScore {
+        positions: positions.len(),
+        agreement: agreed as f64 / positions.len() as f64,
+        average_regret: total_regret / positions.len() as f64,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::expectimax::{DepthPolicy, Expectimax, ExpectimaxConfig};
+    use crate::ai::random::Random;
+    use crate::engine::GameEngineNoStores;
+
+    #[test]
+    fn it_benchmark_suite() {
+        let engine = GameEngineNoStores;
+        let config = ExpectimaxConfig {
+            depth_policy: DepthPolicy::Fixed(1),
+            ..ExpectimaxConfig::default()
+        };
+        let mut labeller = Expectimax::with_config(config);
+        let positions = generate_positions(&mut Random, &mut labeller, &engine, 10, 3);
+        assert_eq!(positions.len(), 10);
+
+        let path = std::env::temp_dir().join("msc_2048_ai_benchmark.csv");
+        let filename = path.to_str().unwrap();
+        save_positions(&positions, filename).unwrap();
+        let loaded = load_positions(filename).unwrap();
+        std::fs::remove_file(path).unwrap();
+        assert_eq!(loaded.len(), positions.len());
+        assert_eq!(loaded[0].board, positions[0].board);
+        assert_eq!(loaded[0].best_move, positions[0].best_move);
+
+        // the labeller always agrees with itself
+        let score = score_agent(&mut labeller, &engine, &positions).unwrap();
+        assert_eq!(score.agreement, 1.);
+        assert_eq!(score.average_regret, 0.);
+        assert!(score_agent(&mut labeller, &engine, &[]).is_none());
+    }
+}
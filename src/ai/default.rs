@@ -1,4 +1,4 @@
-use crate::ai::AI;
+use crate::ai::{MoveScores, ScoreMoves, AI};
 use crate::engine::Board;
 use crate::engine::GameEngine;
 use crate::engine::Move;
@@ -32,3 +32,17 @@ impl AI for Default {
         None
     }
 }
+
+impl ScoreMoves for Default {
+    // The possible moves score the same, they are listed in the order get_next_move tries them so
+    // the tie goes to its move
+    fn score_moves<T: GameEngine>(&mut self, engine: &T, board: Board) -> MoveScores {
+        let mut move_scores = MoveScores::new();
+        for &direction in &[Move::Left, Move::Down, Move::Up, Move::Right] {
+            if engine.make_move(board, direction) != board {
+                move_scores.insert(direction, 1.);
+            }
+        }
+        move_scores
+    }
+}
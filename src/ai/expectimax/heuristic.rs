@@ -0,0 +1,235 @@
+use crate::engine;
+use crate::engine::Board;
+
+// Every possible row/column value, the heuristic score of a board is the sum of the scores of its
+// rows and columns
+const LINE_TABLE_SIZE: usize = 0x10000;
+
+// A heuristic that can be decomposed into a score for each row and column of the board. Each line
+// is scored once up front and stored in a LineTable, so evaluating a board is 8 lookups.
+pub trait Heuristic {
+    fn score_line(&self, line: u64) -> f64;
+
+    fn build_table(&self) -> LineTable {
+        LineTable {
+            scores: (0..LINE_TABLE_SIZE)
+                .map(|line| self.score_line(line as u64))
+                .collect(),
+        }
+    }
+}
+
+// Allows a closure to be used as a heuristic, e.g. |line| engine::line_to_vec(line)[0] as f64
+impl<F: Fn(u64) -> f64> Heuristic for F {
+    fn score_line(&self, line: u64) -> f64 {
+        self(line)
+    }
+}
+
+// Scores the boards at the leaves of the search
+pub trait Evaluator: Sync {
+    fn evaluate(&self, board: Board) -> f64;
+
+    // Evaluators of the future reward of a board (e.g. a learned value function) need the score
+    // gained by each move in the search to be added to the leaf values
+    fn uses_rewards(&self) -> bool {
+        false
+    }
+}
+
+pub struct LineTable {
+    scores: Vec<f64>,
+}
+
+impl LineTable {
+    pub fn get_heurisitic_score(&self, board: Board) -> f64 {
+        let transpose_board = engine::transpose(board);
+        (0..4).fold(0., |score, line_idx| {
+            let row_val = engine::extract_line(board, line_idx);
+            let col_val = engine::extract_line(transpose_board, line_idx);
+            let row_score = unsafe { self.scores.get_unchecked(row_val as usize) };
+            let col_score = unsafe { self.scores.get_unchecked(col_val as usize) };
+            score + row_score + col_score
+        })
+    }
+}
+
+impl Evaluator for LineTable {
+    fn evaluate(&self, board: Board) -> f64 {
+        self.get_heurisitic_score(board)
+    }
+}
+
+// The heuristics developed by Nneonneo were used: https://github.com/nneonneo/2048-ai/blob/master/2048.cpp
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct HeuristicWeights {
+    pub lost_penalty: f64,
+    pub sum_power: f64,
+    pub sum_weight: f64,
+    pub empty_weight: f64,
+    pub merges_weight: f64,
+    pub monotonicity_power: f64,
+    pub monotonicity_weight: f64,
+}
+
+impl Default for HeuristicWeights {
+    fn default() -> Self {
+        HeuristicWeights {
+            lost_penalty: 200000.,
+            sum_power: 3.5,
+            sum_weight: 11.,
+            empty_weight: 270.,
+            merges_weight: 700.,
+            monotonicity_power: 4.,
+            monotonicity_weight: 47.,
+        }
+    }
+}
+
+impl Heuristic for HeuristicWeights {
+    fn score_line(&self, line: u64) -> f64 {
+        calc_heuristic_score(line, self)
+    }
+}
+
+// Scores a line only by how many empty tiles it has
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct EmptyTiles {
+    pub empty_weight: f64,
+}
+
+impl Heuristic for EmptyTiles {
+    fn score_line(&self, line: u64) -> f64 {
+        calc_empty(&engine::line_to_vec(line), self.empty_weight)
+    }
+}
+
+// Rewards large tiles at the start of each line. As rows and columns share the table this pulls
+// the largest tiles towards the top left corner, with the weights decreasing along the snake.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CornerGradient {
+    pub position_weights: [f64; 4],
+}
+
+impl Default for CornerGradient {
+    fn default() -> Self {
+        CornerGradient {
+            position_weights: [8., 4., 2., 1.],
+        }
+    }
+}
+
+impl Heuristic for CornerGradient {
+    fn score_line(&self, line: u64) -> f64 {
+        engine::line_to_vec(line)
+            .iter()
+            .zip(self.position_weights.iter())
+            .fold(0., |acc, (&tile_val, &weight)| {
+                if tile_val == 0 {
+                    acc
+                } else {
+                    acc + weight * (1 << tile_val) as f64
+                }
+            })
+    }
+}
+
+// A score for every possible line, e.g. learned offline
+#[derive(Clone, Debug, PartialEq)]
+pub struct LearnedLines {
+    scores: Vec<f64>,
+}
+
+impl LearnedLines {
+    pub fn new(scores: Vec<f64>) -> Self {
+        assert_eq!(
+            scores.len(),
+            LINE_TABLE_SIZE,
+            "a learned score is needed for every line"
+        );
+        LearnedLines { scores }
+    }
+}
+
+impl Heuristic for LearnedLines {
+    fn score_line(&self, line: u64) -> f64 {
+        self.scores[line as usize]
+    }
+}
+
+fn calc_heuristic_score(line: u64, weights: &HeuristicWeights) -> f64 {
+    let tiles = engine::line_to_vec(line);
+    weights.lost_penalty + calc_empty(&tiles, weights.empty_weight) + calc_merges(&tiles, weights)
+        - calc_monotonicity(&tiles, weights)
+        - calc_sum(&tiles, weights)
+}
+
+fn calc_sum(line: &[u64], weights: &HeuristicWeights) -> f64 {
+    line.iter().fold(0., |acc, &tile_val| {
+        acc + (tile_val as f64).powf(weights.sum_power)
+    }) * weights.sum_weight
+}
+
+fn calc_empty(line: &[u64], empty_weight: f64) -> f64 {
+    line.iter().fold(0., |num_empty_tiles, &tile_val| {
+        if tile_val == 0 {
+            num_empty_tiles + 1.
+        } else {
+            num_empty_tiles
+        }
+    }) * empty_weight
+}
+
+fn calc_merges(line: &[u64], weights: &HeuristicWeights) -> f64 {
+    let mut prev = 0;
+    let mut counter = 0.;
+    let mut merges = 0.;
+    for &tile_val in line {
+        if prev == tile_val && tile_val != 0 {
+            counter += 1.;
+        } else if counter > 0. {
+            merges += 1. + counter;
+            counter = 0.;
+        }
+        prev = tile_val;
+    }
+    if counter > 0. {
+        merges += 1. + counter;
+    }
+    merges * weights.merges_weight
+}
+
+fn calc_monotonicity(line: &[u64], weights: &HeuristicWeights) -> f64 {
+    let power = weights.monotonicity_power;
+    let mut monotonicity_left = 0.;
+    let mut monotonicity_right = 0.;
+    for i in 1..4 {
+        let tile1 = line[i - 1] as f64;
+        let tile2 = line[i] as f64;
+        if tile1 > tile2 {
+            monotonicity_left += tile1.powf(power) - tile2.powf(power);
+        } else {
+            monotonicity_right += tile2.powf(power) - tile1.powf(power);
+        }
+    }
+    monotonicity_left.min(monotonicity_right) * weights.monotonicity_weight
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_build_table() {
+        let table = EmptyTiles { empty_weight: 1. }.build_table();
+        assert_eq!(table.get_heurisitic_score(0), 32.);
+        assert_eq!(table.get_heurisitic_score(0x1000000000000000), 30.);
+        let table = (|line: u64| if line == 0 { 0. } else { 1. }).build_table();
+        assert_eq!(table.get_heurisitic_score(0x0001000000000000), 2.);
+        let table = CornerGradient::default().build_table();
+        assert!(
+            table.get_heurisitic_score(0x3000000000000000)
+                > table.get_heurisitic_score(0x0000000000000003)
+        );
+    }
+}
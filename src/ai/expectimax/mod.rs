@@ -0,0 +1,678 @@
+use self::heuristic::{Evaluator, Heuristic, HeuristicWeights, LineTable};
+use crate::ai::ntuple::NTupleNetwork;
+use crate::ai::{Explain, MoveScores, ScoreMoves, AI};
+use crate::engine;
+use crate::engine::Board;
+use crate::engine::GameEngine;
+use crate::engine::Move;
+use std::collections::HashMap;
+use std::fmt;
+use std::io;
+use std::thread;
+
+pub mod heuristic;
+pub mod tune;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DepthPolicy {
+    // Always search to the same depth
+    Fixed(u64),
+    // Search deeper as more distinct tiles appear on the board: the depth is the number of
+    // distinct tiles minus the offset, clamped between the min and max depth
+    Adaptive {
+        min_depth: u64,
+        max_depth: u64,
+        unique_offset: i32,
+    },
+}
+
+impl DepthPolicy {
+    pub fn get_depth(&self, board: Board) -> u64 {
+        match *self {
+            DepthPolicy::Fixed(depth) => depth,
+            DepthPolicy::Adaptive {
+                min_depth,
+                max_depth,
+                unique_offset,
+            } => {
+                let depth = (count_unique(board) - unique_offset).max(0) as u64;
+                depth.max(min_depth).min(max_depth)
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ExpectimaxConfig {
+    pub depth_policy: DepthPolicy,
+    // Chance nodes reached with a lower cumulative probability are evaluated with the heuristic
+    pub prob_cutoff: f32,
+    // Only used when the leaves are evaluated with the default line heuristic
+    pub weights: HeuristicWeights,
+}
+
+impl Default for ExpectimaxConfig {
+    fn default() -> Self {
+        ExpectimaxConfig {
+            depth_policy: DepthPolicy::Adaptive {
+                min_depth: 3,
+                max_depth: 6,
+                unique_offset: 2,
+            },
+            prob_cutoff: 0.0001,
+            weights: HeuristicWeights::default(),
+        }
+    }
+}
+
+// Three cases:
+//  - max nodes (moves)
+//  - chance nodes (after moves)
+//  - terminal node (depth is 0)
+//
+// Use an enum to represent the three states.
+// Have a seperate function for each of the three cases
+//  - the max function (and expectimax will need to return which move is chosen along with the
+//  value
+
+enum Node {
+    Max,
+    Chance,
+}
+
+type TranspositionTable = HashMap<Board, TranspositionEntry>;
+
+#[derive(Debug)]
+struct ExpectimaxResult {
+    score: f64,
+    move_dir: Option<Move>,
+}
+
+struct TranspositionEntry {
+    score: f64,
+    move_depth: u64,
+}
+
+pub struct Expectimax<E: Evaluator = LineTable> {
+    config: ExpectimaxConfig,
+    evaluator: E,
+}
+
+impl Expectimax {
+    pub fn new() -> Self {
+        Expectimax::with_config(ExpectimaxConfig::default())
+    }
+
+    pub fn with_config(config: ExpectimaxConfig) -> Self {
+        Expectimax::with_heuristic(config, &config.weights)
+    }
+
+    // The heuristic replaces the weights in the config when evaluating leaves
+    pub fn with_heuristic<H: Heuristic + ?Sized>(config: ExpectimaxConfig, heuristic: &H) -> Self {
+        Expectimax::with_evaluator(config, heuristic.build_table())
+    }
+}
+
+impl Expectimax<NTupleNetwork> {
+    // Evaluates the leaves with n-tuple network weights saved by ntuple::train
+    pub fn from_ntuple_file(config: ExpectimaxConfig, filename: &str) -> io::Result<Self> {
+        Ok(Expectimax::with_evaluator(
+            config,
+            NTupleNetwork::load(filename)?,
+        ))
+    }
+}
+
+impl<E: Evaluator> Expectimax<E> {
+    pub fn with_evaluator(config: ExpectimaxConfig, evaluator: E) -> Self {
+        Expectimax { config, evaluator }
+    }
+
+    pub fn get_config(&self) -> ExpectimaxConfig {
+        self.config
+    }
+}
+
+impl Default for Expectimax {
+    fn default() -> Self {
+        Expectimax::new()
+    }
+}
+
+impl<E: Evaluator> AI for Expectimax<E> {
+    fn get_next_move<T: GameEngine>(&mut self, engine: &T, board: Board) -> Option<Move> {
+        let depth = self.config.depth_policy.get_depth(board);
+        self.expectimax(engine, board, Node::Max, depth, 1., &mut HashMap::new())
+            .move_dir
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ExpectimaxExplanation {
+    // The expected value of every possible move, in the order they are searched
+    pub move_values: Vec<(Move, f64)>,
+    pub chosen_move: Option<Move>,
+}
+
+impl fmt::Display for ExpectimaxExplanation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.chosen_move {
+            Some(direction) => writeln!(f, "Move {} has the highest expected value", direction)?,
+            None => writeln!(f, "No move is possible")?,
+        }
+        for (direction, value) in &self.move_values {
+            writeln!(f, "{}: {:.1}", direction, value)?;
+        }
+        Ok(())
+    }
+}
+
+impl<E: Evaluator> Explain for Expectimax<E> {
+    type Explanation = ExpectimaxExplanation;
+
+    fn explain<T: GameEngine>(&mut self, engine: &T, board: Board) -> ExpectimaxExplanation {
+        let move_scores = self.score_moves(engine, board);
+        ExpectimaxExplanation {
+            move_values: move_scores.get_scores().clone(),
+            chosen_move: move_scores.best(),
+        }
+    }
+}
+
+impl<E: Evaluator> ScoreMoves for Expectimax<E> {
+    // Runs the same search as get_next_move, keeping the expected value of each root move
+    fn score_moves<T: GameEngine>(&mut self, engine: &T, board: Board) -> MoveScores {
+        let move_depth = self.config.depth_policy.get_depth(board);
+        let mut map = HashMap::new();
+        let mut move_scores = MoveScores::new();
+        for &direction in &[Move::Up, Move::Down, Move::Left, Move::Right] {
+            let new_board = engine.shift(board, direction);
+            if new_board == board {
+                continue;
+            }
+            let mut score = self
+                .expectimax(engine, new_board, Node::Chance, move_depth, 1., &mut map)
+                .score;
+            if self.evaluator.uses_rewards() {
+                score += (engine.get_score(new_board) - engine.get_score(board)) as f64;
+            }
+            move_scores.insert(direction, score);
+        }
+        move_scores
+    }
+}
+
+impl<E: Evaluator> Expectimax<E> {
+    fn expectimax<T: GameEngine>(
+        &self,
+        engine: &T,
+        board: Board,
+        node: Node,
+        move_depth: u64,
+        cum_prob: f32,
+        map: &mut TranspositionTable,
+    ) -> ExpectimaxResult {
+        match node {
+            Node::Max => return self.evaluate_max(engine, board, move_depth, cum_prob, map),
+            Node::Chance => return self.evaluate_chance(engine, board, move_depth, cum_prob, map),
+        }
+    }
+
+    fn evaluate_max<T: GameEngine>(
+        &self,
+        engine: &T,
+        board: Board,
+        move_depth: u64,
+        cum_prob: f32,
+        map: &mut TranspositionTable,
+    ) -> ExpectimaxResult {
+        let mut best_score = 0.;
+        let mut best_move = None;
+        for &direction in &[Move::Up, Move::Down, Move::Left, Move::Right] {
+            let new_board;
+            match direction {
+                Move::Up => new_board = engine.shift(board, Move::Up),
+                Move::Down => new_board = engine.shift(board, Move::Down),
+                Move::Left => new_board = engine.shift(board, Move::Left),
+                Move::Right => new_board = engine.shift(board, Move::Right),
+            }
+            if new_board != board {
+                let mut score = self
+                    .expectimax(engine, new_board, Node::Chance, move_depth, cum_prob, map)
+                    .score;
+                if self.evaluator.uses_rewards() {
+                    score += (engine.get_score(new_board) - engine.get_score(board)) as f64;
+                }
+                // a lost board scores 0, any legal move is played even if it scores less
+                if best_move.is_none() || score > best_score {
+                    best_score = score;
+                    best_move = Some(direction);
+                }
+            }
+        }
+        ExpectimaxResult {
+            score: best_score,
+            move_dir: best_move,
+        }
+    }
+
+    fn evaluate_chance<T: GameEngine>(
+        &self,
+        engine: &T,
+        board: Board,
+        move_depth: u64,
+        cum_prob: f32,
+        map: &mut TranspositionTable,
+    ) -> ExpectimaxResult {
+        if move_depth == 0 || cum_prob < self.config.prob_cutoff {
+            return ExpectimaxResult {
+                score: self.evaluator.evaluate(board),
+                move_dir: None,
+            };
+        }
+
+        // Check if board has already been seen
+        if let Some(entry) = map.get(&board) {
+            // need to check depth is greater than or equal to current depth
+            // if depth is less then the score will not be accurate enough
+            if entry.move_depth >= move_depth {
+                return ExpectimaxResult {
+                    score: entry.score,
+                    move_dir: None,
+                };
+            }
+        }
+
+        let num_empty_tiles = engine::count_empty(board);
+        let mut tiles_searched = 0;
+        let mut tmp = board;
+        let mut insert_tile = 1;
+        let mut score = 0.;
+        let cum_prob = cum_prob / num_empty_tiles as f32;
+
+        while tiles_searched < num_empty_tiles {
+            if (tmp & 0xf) == 0 {
+                let new_board = board | insert_tile;
+                score += self
+                    .expectimax(
+                        engine,
+                        new_board,
+                        Node::Max,
+                        move_depth - 1,
+                        cum_prob * 0.9,
+                        map,
+                    )
+                    .score
+                    * 0.9;
+
+                let new_board = board | (insert_tile << 1);
+                score += self
+                    .expectimax(
+                        engine,
+                        new_board,
+                        Node::Max,
+                        move_depth - 1,
+                        cum_prob * 0.1,
+                        map,
+                    )
+                    .score
+                    * 0.1;
+
+                tiles_searched += 1;
+            }
+            tmp >>= 4;
+            insert_tile <<= 4;
+        }
+
+        score = score / num_empty_tiles as f64;
+
+        map.insert(board, TranspositionEntry { score, move_depth });
+
+        ExpectimaxResult {
+            score,
+            move_dir: None,
+        }
+    }
+}
+
+// Splits the search across the root moves and the first chance nodes. Each spawn after a root
+// move is a separate task, the tasks are dealt out to the threads in a fixed order and every
+// thread keeps its own transposition table, so the result does not depend on thread scheduling.
+// With one thread the tasks are searched in the same order as the single threaded search and the
+// scores match exactly. With more threads a table can reuse a score from a different cumulative
+// probability than the single threaded search would, so the scores can differ slightly and the
+// move can differ between moves that score almost the same.
+pub struct ExpectimaxMultithread<E: Evaluator = LineTable> {
+    expectimax: Expectimax<E>,
+    threads: usize,
+}
+
+impl ExpectimaxMultithread {
+    pub fn new(threads: usize) -> Self {
+        ExpectimaxMultithread::with_config(ExpectimaxConfig::default(), threads)
+    }
+
+    pub fn with_config(config: ExpectimaxConfig, threads: usize) -> Self {
+        ExpectimaxMultithread::from_expectimax(Expectimax::with_config(config), threads)
+    }
+}
+
+impl<E: Evaluator> ExpectimaxMultithread<E> {
+    pub fn from_expectimax(expectimax: Expectimax<E>, threads: usize) -> Self {
+        assert!(threads > 0, "at least one thread is needed");
+        ExpectimaxMultithread {
+            expectimax,
+            threads,
+        }
+    }
+}
+
+struct ChanceTask {
+    move_idx: usize,
+    board: Board,
+    prob: f64,
+}
+
+impl<E: Evaluator> AI for ExpectimaxMultithread<E> {
+    fn get_next_move<T: GameEngine>(&mut self, engine: &T, board: Board) -> Option<Move> {
+        let depth = self.expectimax.config.depth_policy.get_depth(board);
+        if depth == 0 {
+            return self.expectimax.get_next_move(engine, board);
+        }
+        self.evaluate_multithread(engine, board, depth).best()
+    }
+}
+
+impl<E: Evaluator> ScoreMoves for ExpectimaxMultithread<E> {
+    // The expected value of each root move from the same search as get_next_move
+    fn score_moves<T: GameEngine>(&mut self, engine: &T, board: Board) -> MoveScores {
+        let depth = self.expectimax.config.depth_policy.get_depth(board);
+        if depth == 0 {
+            return self.expectimax.score_moves(engine, board);
+        }
+        self.evaluate_multithread(engine, board, depth)
+    }
+}
+
+impl<E: Evaluator> ExpectimaxMultithread<E> {
+    // The expected value of every possible root move, in the order they are searched
+    fn evaluate_multithread<T: GameEngine>(
+        &self,
+        engine: &T,
+        board: Board,
+        move_depth: u64,
+    ) -> MoveScores {
+        let directions = [Move::Up, Move::Down, Move::Left, Move::Right];
+        let mut tasks = Vec::new();
+        let mut num_empty_tiles = [0; 4];
+        for (move_idx, &direction) in directions.iter().enumerate() {
+            let new_board = engine.shift(board, direction);
+            if new_board == board {
+                continue;
+            }
+            num_empty_tiles[move_idx] = engine::count_empty(new_board);
+            let mut tmp = new_board;
+            let mut insert_tile = 1;
+            let mut tiles_searched = 0;
+            while tiles_searched < num_empty_tiles[move_idx] {
+                if (tmp & 0xf) == 0 {
+                    tasks.push(ChanceTask {
+                        move_idx,
+                        board: new_board | insert_tile,
+                        prob: 0.9,
+                    });
+                    tasks.push(ChanceTask {
+                        move_idx,
+                        board: new_board | (insert_tile << 1),
+                        prob: 0.1,
+                    });
+                    tiles_searched += 1;
+                }
+                tmp >>= 4;
+                insert_tile <<= 4;
+            }
+        }
+
+        let expectimax = &self.expectimax;
+        let tasks = &tasks;
+        let threads = self.threads;
+        let results = thread::scope(|scope| {
+            let handles = (0..threads)
+                .map(|thread_idx| {
+                    scope.spawn(move || {
+                        let mut map = HashMap::new();
+                        tasks
+                            .iter()
+                            .enumerate()
+                            .skip(thread_idx)
+                            .step_by(threads)
+                            .map(|(task_idx, task)| {
+                                // the same operations as evaluate_chance so the cutoffs match
+                                let cum_prob =
+                                    1. / num_empty_tiles[task.move_idx] as f32 * task.prob as f32;
+                                let score = expectimax
+                                    .expectimax(
+                                        engine,
+                                        task.board,
+                                        Node::Max,
+                                        move_depth - 1,
+                                        cum_prob,
+                                        &mut map,
+                                    )
+                                    .score;
+                                (task_idx, score)
+                            })
+                            .collect::<Vec<_>>()
+                    })
+                })
+                .collect::<Vec<_>>();
+            handles
+                .into_iter()
+                .flat_map(|handle| handle.join().expect("expectimax thread panicked"))
+                .collect::<Vec<_>>()
+        });
+
+        // sum in task order so the floating point result matches the single threaded search
+        let mut task_scores = vec![0.; tasks.len()];
+        for (task_idx, score) in results {
+            task_scores[task_idx] = score;
+        }
+        let mut move_scores = [0.; 4];
+        for (task, score) in tasks.iter().zip(task_scores) {
+            move_scores[task.move_idx] += score * task.prob;
+        }
+
+        // best picks the first of equal scores like evaluate_max
+        let mut move_values = MoveScores::new();
+        for (move_idx, &direction) in directions.iter().enumerate() {
+            if num_empty_tiles[move_idx] == 0 {
+                continue;
+            }
+            let mut score = move_scores[move_idx] / num_empty_tiles[move_idx] as f64;
+            if self.expectimax.evaluator.uses_rewards() {
+                let new_board = engine.shift(board, direction);
+                score += (engine.get_score(new_board) - engine.get_score(board)) as f64;
+            }
+            move_values.insert(direction, score);
+        }
+        move_values
+    }
+}
+
+// Credit to Nneonneo
+fn count_unique(board: Board) -> i32 {
+    let mut bitset = 0;
+    let mut board_copy = board;
+    while board_copy != 0 {
+        bitset |= 1 << (board_copy & 0xf);
+        board_copy >>= 4;
+    }
+
+    // Don't count empty tiles.
+    bitset >>= 1;
+
+    let mut count = 0;
+    while bitset != 0 {
+        bitset &= bitset - 1;
+        count += 1;
+    }
+    return count;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::GameEngineNoStores;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    #[test]
+    fn it_count_unique() {
+        let game = 0x1134000000000000;
+        assert_eq!(count_unique(game), 3);
+        let game = 0x0000010000000010;
+        assert_eq!(count_unique(game), 1);
+    }
+
+    #[test]
+    fn it_depth_policy() {
+        let policy = ExpectimaxConfig::default().depth_policy;
+        assert_eq!(policy.get_depth(0x1134000000000000), 3);
+        assert_eq!(policy.get_depth(0x1234567000000000), 5);
+        assert_eq!(policy.get_depth(0x123456789abc0000), 6);
+        assert_eq!(DepthPolicy::Fixed(2).get_depth(0x123456789abc0000), 2);
+    }
+
+    // Boards from seeded random games, from the opening to the end game
+    fn seeded_boards(engine: &GameEngineNoStores, num_boards: usize) -> Vec<Board> {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut boards = vec![0x1234432112344321];
+        while boards.len() < num_boards {
+            let mut board = engine::new_board_with_rng(&mut rng);
+            let mut num_moves = 0;
+            while !engine.is_game_over(board) {
+                if num_moves % 25 == 0 {
+                    boards.push(board);
+                }
+                let direction = Move::iterator()
+                    .nth(rng.gen_range(0, 4))
+                    .expect("there are four moves");
+                board = engine.make_move_with_rng(board, direction, &mut rng);
+                num_moves += 1;
+            }
+        }
+        boards.truncate(num_boards);
+        boards
+    }
+
+    #[test]
+    fn it_multithread_same_move() {
+        let engine = GameEngineNoStores;
+        let boards = seeded_boards(&engine, 20);
+        for &depth in &[1, 2, 3] {
+            let config = ExpectimaxConfig {
+                depth_policy: DepthPolicy::Fixed(depth),
+                ..ExpectimaxConfig::default()
+            };
+            let mut expectimax = Expectimax::with_config(config);
+            let mut one_thread = ExpectimaxMultithread::with_config(config, 1);
+            let mut multithread = ExpectimaxMultithread::with_config(config, 3);
+            for &board in &boards {
+                let expected = expectimax.score_moves(&engine, board);
+                // one thread searches the tasks in the same order with one table
+                assert_eq!(
+                    one_thread.score_moves(&engine, board),
+                    expected,
+                    "depth {} board {:016x}",
+                    depth,
+                    board
+                );
+                // more threads can reuse different table entries, the move is then one the
+                // single threaded search scores as good as the best
+                let scores = multithread.score_moves(&engine, board);
+                assert_eq!(multithread.score_moves(&engine, board), scores);
+                if let Some(best_move) = expected.best() {
+                    let best = expected.get(best_move).unwrap();
+                    let chosen = expected.get(scores.best().unwrap()).unwrap();
+                    assert!(
+                        best - chosen <= best.abs() * 1e-4,
+                        "depth {} board {:016x}",
+                        depth,
+                        board
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn it_explain_expectimax() {
+        let engine = GameEngineNoStores;
+        let mut expectimax = Expectimax::with_config(ExpectimaxConfig {
+            depth_policy: DepthPolicy::Fixed(2),
+            ..ExpectimaxConfig::default()
+        });
+        let board = 0x0000012000300000;
+        let explanation = expectimax.explain(&engine, board);
+        assert_eq!(explanation.move_values.len(), 4);
+        let (best_move, _) = explanation.move_values.iter().fold(
+            (None, 0.),
+            |(best_move, best_value), &(direction, value)| {
+                if value > best_value {
+                    (Some(direction), value)
+                } else {
+                    (best_move, best_value)
+                }
+            },
+        );
+        assert_eq!(explanation.chosen_move, best_move);
+        assert_eq!(
+            explanation.chosen_move,
+            expectimax.get_next_move(&engine, board)
+        );
+        let explanation = expectimax.explain(&engine, 0x1234432112344321);
+        assert!(explanation.move_values.is_empty());
+        assert_eq!(explanation.chosen_move, None);
+    }
+
+    #[test]
+    fn it_ntuple_evaluator() {
+        let engine = GameEngineNoStores;
+        let config = ExpectimaxConfig {
+            depth_policy: DepthPolicy::Fixed(1),
+            ..ExpectimaxConfig::default()
+        };
+        // the untrained network values every board at 0, so the merge reward decides the move
+        let network = NTupleNetwork::new(vec![vec![0, 1, 2, 3], vec![4, 5, 6, 7]]);
+        let mut expectimax = Expectimax::with_evaluator(config, network);
+        assert_eq!(
+            expectimax.get_next_move(&engine, 0x1100200000000000),
+            Some(Move::Left)
+        );
+        assert_eq!(expectimax.get_next_move(&engine, 0x1234432112344321), None);
+        let mut multithread = ExpectimaxMultithread::from_expectimax(expectimax, 2);
+        assert_eq!(
+            multithread.get_next_move(&engine, 0x1100200000000000),
+            Some(Move::Left)
+        );
+    }
+
+    #[test]
+    fn it_heuristic_table_per_instance() {
+        let default = Expectimax::new();
+        let config = ExpectimaxConfig {
+            weights: HeuristicWeights {
+                empty_weight: 0.,
+                ..HeuristicWeights::default()
+            },
+            ..ExpectimaxConfig::default()
+        };
+        let no_empty = Expectimax::with_config(config);
+        let board = 0x1200000000000000;
+        assert!(default.evaluator.evaluate(board) > no_empty.evaluator.evaluate(board));
+        assert_eq!(
+            Expectimax::new().evaluator.evaluate(board),
+            default.evaluator.evaluate(board)
+        );
+    }
+}
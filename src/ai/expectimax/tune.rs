@@ -0,0 +1,306 @@
+use super::heuristic::HeuristicWeights;
+use super::{DepthPolicy, Expectimax, ExpectimaxConfig};
+use crate::ai::strategy::mann_whitney::{mann_whitney_u_test, Confidence};
+use crate::ai::AI;
+use crate::engine::{new_board_with_rng, GameEngine, Score};
+use rand::distributions::{Distribution, Normal};
+use rand::rngs::StdRng;
+use rand::{thread_rng, SeedableRng};
+use std::cmp::Ordering;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::Path;
+
+// The weights are tuned using the cross-entropy method: each generation a population of weights
+// is sampled from a normal distribution per weight, every candidate plays the same seeded games
+// and the distribution is refit to the elite candidates.
+#[derive(Clone, Copy, Debug)]
+pub struct TuneConfig {
+    // Fixed search depth used while tuning, keep it shallow so a generation is quick
+    pub depth: u64,
+    pub games: usize,
+    pub generations: usize,
+    pub population: usize,
+    pub elite: usize,
+    // Standard deviation of each weight at the start, relative to its starting value
+    pub initial_spread: f64,
+}
+
+impl Default for TuneConfig {
+    fn default() -> Self {
+        TuneConfig {
+            depth: 1,
+            games: 20,
+            generations: 20,
+            population: 16,
+            elite: 4,
+            initial_spread: 0.5,
+        }
+    }
+}
+
+const NUM_WEIGHTS: usize = 7;
+
+// Saves the tuned weights to the file along with the configuration they were tuned with
+pub fn tune_weights<T: GameEngine>(
+    engine: &T,
+    config: TuneConfig,
+    filename: &str,
+) -> io::Result<HeuristicWeights> {
+    // the averages and the comparison need games, and there is no tuned result without a
+    // generation
+    assert!(config.games > 0 && config.generations > 0);
+    assert!(config.elite > 0 && config.elite <= config.population);
+    println!("Starting heuristic weight tuning...");
+    let initial = HeuristicWeights::default();
+    let mut means = to_vec(&initial);
+    let mut std_devs = means
+        .iter()
+        .map(|mean| mean.abs() * config.initial_spread)
+        .collect::<Vec<_>>();
+    let mut rng = thread_rng();
+
+    let mut best_weights = initial;
+    let mut best_score = average(&play_seeded_games(
+        engine,
+        &initial,
+        config.depth,
+        0,
+        config.games,
+    ));
+    println!("Starting average score: {}", best_score);
+
+    for generation in 0..config.generations {
+        let mut candidates = (0..config.population)
+            .map(|_| {
+                let sample = means
+                    .iter()
+                    .zip(std_devs.iter())
+                    .map(|(&mean, &std_dev)| Normal::new(mean, std_dev).sample(&mut rng).max(0.))
+                    .collect::<Vec<_>>();
+                let weights = from_vec(&sample);
+                // every candidate plays the same games so they are compared fairly
+                let score = average(&play_seeded_games(
+                    engine,
+                    &weights,
+                    config.depth,
+                    0,
+                    config.games,
+                ));
+                (sample, score)
+            })
+            .collect::<Vec<_>>();
+        candidates.sort_by(|(_, a), (_, b)| b.partial_cmp(a).unwrap_or(Ordering::Equal));
+
+        let elite = &candidates[..config.elite];
+        for idx in 0..NUM_WEIGHTS {
+            let mean =
+                elite.iter().map(|(sample, _)| sample[idx]).sum::<f64>() / config.elite as f64;
+            let variance = elite
+                .iter()
+                .map(|(sample, _)| (sample[idx] - mean).powi(2))
+                .sum::<f64>()
+                / config.elite as f64;
+            means[idx] = mean;
+            std_devs[idx] = variance.sqrt();
+        }
+
+        let (generation_best, generation_score) = &candidates[0];
+        if *generation_score > best_score {
+            best_score = *generation_score;
+            best_weights = from_vec(generation_best);
+        }
+        println!(
+            "Generation {}/{}: best average {}, overall best average {}",
+            generation + 1,
+            config.generations,
+            generation_score,
+            best_score
+        );
+    }
+
+    report_comparison(engine, &initial, &best_weights, config);
+    save_tuned_weights(&best_weights, config, filename)?;
+    Ok(best_weights)
+}
+
+// Plays fresh games, not used during tuning, with the starting and tuned weights and compares
+// them with the Mann-Whitney U test
+fn report_comparison<T: GameEngine>(
+    engine: &T,
+    initial: &HeuristicWeights,
+    tuned: &HeuristicWeights,
+    config: TuneConfig,
+) {
+    let first_seed = config.games as u64;
+    let initial_scores = play_seeded_games(engine, initial, config.depth, first_seed, config.games);
+    let tuned_scores = play_seeded_games(engine, tuned, config.depth, first_seed, config.games);
+    let comparison = match mann_whitney_u_test(&tuned_scores, &initial_scores, Confidence::P01) {
+        Ordering::Greater => "better than",
+        Ordering::Equal => "not significantly different to",
+        Ordering::Less => "worse than",
+    };
+    println!(
+        "\nTuned weights: {:?}\nAverage score: {} (starting weights: {})\nThe tuned weights are {} the starting weights",
+        tuned,
+        average(&tuned_scores),
+        average(&initial_scores),
+        comparison
+    );
+}
+
+fn play_seeded_games<T: GameEngine>(
+    engine: &T,
+    weights: &HeuristicWeights,
+    depth: u64,
+    first_seed: u64,
+    games: usize,
+) -> Vec<Score> {
+    let mut expectimax = Expectimax::with_config(ExpectimaxConfig {
+        depth_policy: DepthPolicy::Fixed(depth),
+        weights: *weights,
+        ..ExpectimaxConfig::default()
+    });
+    (first_seed..first_seed + games as u64)
+        .map(|seed| {
+            let mut rng = StdRng::seed_from_u64(seed);
+            let mut board = new_board_with_rng(&mut rng);
+            while let Some(direction) = expectimax.get_next_move(engine, board) {
+                board = engine.make_move_with_rng(board, direction, &mut rng);
+            }
+            engine.get_score(board)
+        })
+        .collect()
+}
+
+pub fn save_weights(weights: &HeuristicWeights, filename: &str) -> io::Result<()> {
+    let mut f = File::create(Path::new(filename))?;
+    write_weights(&mut f, weights)
+}
+
+// The configuration is written as comments so the file can still be loaded with load_weights.
+// Tuning plays the games with seeds 0 to games - 1 and the comparison the next games seeds.
+fn save_tuned_weights(
+    weights: &HeuristicWeights,
+    config: TuneConfig,
+    filename: &str,
+) -> io::Result<()> {
+    let mut f = File::create(Path::new(filename))?;
+    let settings = [
+        ("depth", config.depth.to_string()),
+        ("games", config.games.to_string()),
+        ("generations", config.generations.to_string()),
+        ("population", config.population.to_string()),
+        ("elite", config.elite.to_string()),
+        ("initial_spread", config.initial_spread.to_string()),
+        ("tuning seeds", format!("0..{}", config.games)),
+        (
+            "comparison seeds",
+            format!("{}..{}", config.games, config.games * 2),
+        ),
+    ];
+    for (name, value) in settings.iter() {
+        f.write_fmt(format_args!("# {}={}\n", name, value))?;
+    }
+    write_weights(&mut f, weights)
+}
+
+fn write_weights(f: &mut File, weights: &HeuristicWeights) -> io::Result<()> {
+    for (name, value) in WEIGHT_NAMES.iter().zip(to_vec(weights)) {
+        f.write_fmt(format_args!("{}={}\n", name, value))?;
+    }
+    Ok(())
+}
+
+// Weights missing from the file keep their default value, lines starting with # are ignored
+pub fn load_weights(filename: &str) -> io::Result<HeuristicWeights> {
+    let f = File::open(Path::new(filename))?;
+    let mut values = to_vec(&HeuristicWeights::default());
+    for line in BufReader::new(f).lines() {
+        let line = line?;
+        if line.trim().is_empty() || line.trim_start().starts_with('#') {
+            continue;
+        }
+        let invalid_line = || {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("invalid line: {}", line),
+            )
+        };
+        let mut parts = line.splitn(2, '=');
+        let name = parts.next().ok_or_else(invalid_line)?.trim();
+        let value = parts
+            .next()
+            .and_then(|value| value.trim().parse::<f64>().ok())
+            .ok_or_else(invalid_line)?;
+        let idx = WEIGHT_NAMES
+            .iter()
+            .position(|&weight_name| weight_name == name)
+            .ok_or_else(invalid_line)?;
+        values[idx] = value;
+    }
+    Ok(from_vec(&values))
+}
+
+const WEIGHT_NAMES: [&str; NUM_WEIGHTS] = [
+    "lost_penalty",
+    "sum_power",
+    "sum_weight",
+    "empty_weight",
+    "merges_weight",
+    "monotonicity_power",
+    "monotonicity_weight",
+];
+
+fn to_vec(weights: &HeuristicWeights) -> Vec<f64> {
+    vec![
+        weights.lost_penalty,
+        weights.sum_power,
+        weights.sum_weight,
+        weights.empty_weight,
+        weights.merges_weight,
+        weights.monotonicity_power,
+        weights.monotonicity_weight,
+    ]
+}
+
+fn from_vec(values: &[f64]) -> HeuristicWeights {
+    HeuristicWeights {
+        lost_penalty: values[0],
+        sum_power: values[1],
+        sum_weight: values[2],
+        empty_weight: values[3],
+        merges_weight: values[4],
+        monotonicity_power: values[5],
+        monotonicity_weight: values[6],
+    }
+}
+
+fn average(scores: &[Score]) -> f64 {
+    scores.iter().fold(0., |acc, &score| acc + score as f64) / scores.len() as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_save_load_weights() {
+        let weights = HeuristicWeights {
+            sum_power: 2.75,
+            merges_weight: 512.5,
+            ..HeuristicWeights::default()
+        };
+        let path = std::env::temp_dir().join("msc_2048_ai_tuned_weights.txt");
+        let filename = path.to_str().unwrap();
+        save_weights(&weights, filename).unwrap();
+        assert_eq!(load_weights(filename).unwrap(), weights);
+        // the configuration is saved alongside the tuned weights
+        save_tuned_weights(&weights, TuneConfig::default(), filename).unwrap();
+        let saved = std::fs::read_to_string(&path).unwrap();
+        assert!(saved.starts_with("# depth=1\n# games=20\n"));
+        assert!(saved.contains("# tuning seeds=0..20\n# comparison seeds=20..40\n"));
+        assert_eq!(load_weights(filename).unwrap(), weights);
+        std::fs::remove_file(path).unwrap();
+    }
+}
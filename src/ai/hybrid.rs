@@ -0,0 +1,240 @@
+use crate::ai::expectimax::heuristic::{Evaluator, LineTable};
+use crate::ai::expectimax::{DepthPolicy, Expectimax, ExpectimaxConfig};
+use crate::ai::strategy::attributes;
+use crate::ai::strategy::attributes::Corner;
+use crate::ai::strategy::Strategy;
+use crate::ai::{MoveScores, ScoreMoves, AI};
+use crate::engine;
+use crate::engine::{Board, GameEngine, Move};
+use std::fmt;
+
+// Follows a rule strategy but hands the move over to a shallow expectimax search whenever one of
+// the danger conditions holds. Every move is counted so the cost of readable play can be measured,
+// along with which conditions caused the switches.
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DangerCondition {
+    // Fewer than this many empty tiles
+    FewEmptyTiles(u64),
+    LargestTileNotInCorner(Corner),
+    // No try rule or fallback move is allowed so the strategy has to make a banned move
+    ForcedBannedMove,
+}
+
+impl DangerCondition {
+    // The strategy move is passed in so the strategy does not need to be run twice
+    fn holds<T: GameEngine>(
+        &self,
+        engine: &T,
+        strategy: &Strategy,
+        board: Board,
+        strategy_move: Move,
+    ) -> bool {
+        match *self {
+            DangerCondition::FewEmptyTiles(num_empty) => engine::count_empty(board) < num_empty,
+            DangerCondition::LargestTileNotInCorner(corner) => {
+                !attributes::is_largest_tile_in_corner(board, corner)
+            }
+            DangerCondition::ForcedBannedMove => strategy
+                .get_banned_moves(engine, board)
+                .contains(&strategy_move),
+        }
+    }
+}
+
+impl fmt::Display for DangerCondition {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DangerCondition::FewEmptyTiles(num_empty) => {
+                write!(f, "fewer than {} empty tiles", num_empty)
+            }
+            DangerCondition::LargestTileNotInCorner(corner) => {
+                write!(f, "largest tile not in {} corner", corner)
+            }
+            DangerCondition::ForcedBannedMove => write!(f, "strategy forced to make banned move"),
+        }
+    }
+}
+
+pub struct Hybrid<E: Evaluator = LineTable> {
+    strategy: Strategy,
+    expectimax: Expectimax<E>,
+    conditions: Vec<DangerCondition>,
+    strategy_moves: u64,
+    search_moves: u64,
+    // number of moves each condition held for, matches the order of the conditions
+    condition_counts: Vec<u64>,
+}
+
+impl Hybrid {
+    // Searches to a fixed depth with the default heuristic
+    pub fn new(strategy: Strategy, conditions: Vec<DangerCondition>, depth: u64) -> Self {
+        Hybrid::with_expectimax(
+            strategy,
+            conditions,
+            Expectimax::with_config(ExpectimaxConfig {
+                depth_policy: DepthPolicy::Fixed(depth),
+                ..ExpectimaxConfig::default()
+            }),
+        )
+    }
+}
+
+impl<E: Evaluator> Hybrid<E> {
+    pub fn with_expectimax(
+        strategy: Strategy,
+        conditions: Vec<DangerCondition>,
+        expectimax: Expectimax<E>,
+    ) -> Self {
+        let num_conditions = conditions.len();
+        Hybrid {
+            strategy,
+            expectimax,
+            conditions,
+            strategy_moves: 0,
+            search_moves: 0,
+            condition_counts: vec![0; num_conditions],
+        }
+    }
+
+    pub fn get_strategy_moves(&self) -> u64 {
+        self.strategy_moves
+    }
+
+    pub fn get_search_moves(&self) -> u64 {
+        self.search_moves
+    }
+
+    pub fn get_condition_counts(&self) -> Vec<(DangerCondition, u64)> {
+        self.conditions
+            .iter()
+            .cloned()
+            .zip(self.condition_counts.iter().cloned())
+            .collect()
+    }
+
+    pub fn reset_counts(&mut self) {
+        self.strategy_moves = 0;
+        self.search_moves = 0;
+        self.condition_counts
+            .iter_mut()
+            .for_each(|count| *count = 0);
+    }
+}
+
+impl<E: Evaluator> AI for Hybrid<E> {
+    fn get_next_move<T: GameEngine>(&mut self, engine: &T, board: Board) -> Option<Move> {
+        let strategy_move = self.strategy.get_next_move(engine, board)?;
+        let mut in_danger = false;
+        for (condition, count) in self.conditions.iter().zip(self.condition_counts.iter_mut()) {
+            if condition.holds(engine, &self.strategy, board, strategy_move) {
+                *count += 1;
+                in_danger = true;
+            }
+        }
+        if in_danger {
+            self.search_moves += 1;
+            self.expectimax.get_next_move(engine, board)
+        } else {
+            self.strategy_moves += 1;
+            Some(strategy_move)
+        }
+    }
+}
+
+impl<E: Evaluator> ScoreMoves for Hybrid<E> {
+    // The scores of whichever agent get_next_move would hand the move to, the switch counts are
+    // not changed
+    fn score_moves<T: GameEngine>(&mut self, engine: &T, board: Board) -> MoveScores {
+        let strategy_move = match self.strategy.get_next_move(engine, board) {
+            Some(strategy_move) => strategy_move,
+            None => return MoveScores::new(),
+        };
+        let strategy = &self.strategy;
+        if self
+            .conditions
+            .iter()
+            .any(|condition| condition.holds(engine, strategy, board, strategy_move))
+        {
+            self.expectimax.score_moves(engine, board)
+        } else {
+            self.strategy.score_moves(engine, board)
+        }
+    }
+}
+
+impl<E: Evaluator> fmt::Display for Hybrid<E> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let total_moves = self.strategy_moves + self.search_moves;
+        let search_percentage = if total_moves == 0 {
+            0.
+        } else {
+            self.search_moves as f64 / total_moves as f64 * 100.
+        };
+        write!(
+            f,
+            "Strategy: {}\nStrategy moves: {}, search moves: {} ({:.1}%)",
+            self.strategy, self.strategy_moves, self.search_moves, search_percentage
+        )?;
+        for (condition, count) in self.get_condition_counts() {
+            write!(f, "\n{}: {}", condition, count)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::strategy::attributes::Column;
+    use crate::ai::strategy::ban_rules::BanMove;
+    use crate::engine::GameEngineNoStores;
+
+    #[test]
+    fn it_hybrid_switch_counts() {
+        let engine = GameEngineNoStores;
+        let strategy = Strategy::new(
+            &vec![BanMove::IfColumnNotLocked(Move::Up, Column::Left)],
+            &vec![],
+            &vec![Move::Left, Move::Up, Move::Down, Move::Right],
+        )
+        .unwrap();
+        let mut hybrid = Hybrid::new(
+            strategy,
+            vec![
+                DangerCondition::FewEmptyTiles(2),
+                DangerCondition::ForcedBannedMove,
+            ],
+            1,
+        );
+        // plenty of space, the strategy moves left
+        assert_eq!(
+            hybrid.get_next_move(&engine, 0x0100020000000000),
+            Some(Move::Left)
+        );
+        // only up is possible and it is banned
+        assert_eq!(
+            hybrid.get_next_move(&engine, 0x0000000000001234),
+            Some(Move::Up)
+        );
+        assert_eq!(hybrid.get_strategy_moves(), 1);
+        assert_eq!(hybrid.get_search_moves(), 1);
+        assert_eq!(
+            hybrid.get_condition_counts(),
+            vec![
+                (DangerCondition::FewEmptyTiles(2), 0),
+                (DangerCondition::ForcedBannedMove, 1)
+            ]
+        );
+        assert_eq!(hybrid.get_next_move(&engine, 0x1234432112344321), None);
+        assert_eq!(
+            hybrid.score_moves(&engine, 0x0100020000000000).best(),
+            Some(Move::Left)
+        );
+        assert_eq!(
+            hybrid.score_moves(&engine, 0x0000000000001234).best(),
+            Some(Move::Up)
+        );
+        assert_eq!(hybrid.get_search_moves(), 1);
+    }
+}
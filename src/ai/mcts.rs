@@ -0,0 +1,284 @@
+use crate::ai::{play_out, MoveScores, ScoreMoves, AI};
+use crate::engine;
+use crate::engine::{Board, GameEngine, Move, Score};
+use rand::rngs::ThreadRng;
+use rand::thread_rng;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+// Monte Carlo tree search over the game tree with explicit chance nodes for the tile spawns.
+//  - decision nodes are boards where the player picks a move, children are picked with UCT
+//  - chance nodes are the boards after a move, before a tile spawns, children are sampled
+//  - once a new chance node is reached the rest of the game is played by the rollout policy
+// The reward of a rollout is the final score, normalised by the largest reward seen so that the
+// exploration constant does not depend on how far into the game the search is.
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Budget {
+    Iterations(usize),
+    Time(Duration),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MctsConfig {
+    pub budget: Budget,
+    pub exploration: f64,
+    // Maximum number of moves in a rollout, None plays until the game is over
+    pub rollout_depth: Option<usize>,
+}
+
+impl Default for MctsConfig {
+    fn default() -> Self {
+        MctsConfig {
+            budget: Budget::Iterations(1000),
+            exploration: 2_f64.sqrt(),
+            rollout_depth: None,
+        }
+    }
+}
+
+// Any AI can be used as the rollout policy, e.g. Random or a Strategy
+pub struct Mcts<P: AI> {
+    config: MctsConfig,
+    rollout_policy: P,
+    rng: ThreadRng,
+}
+
+impl<P: AI> Mcts<P> {
+    pub fn new(config: MctsConfig, rollout_policy: P) -> Self {
+        Mcts {
+            config,
+            rollout_policy,
+            rng: thread_rng(),
+        }
+    }
+}
+
+struct DecisionNode {
+    board: Board,
+    visits: u64,
+    // (move, chance node idx), empty until the node is expanded
+    children: Vec<(Move, usize)>,
+    expanded: bool,
+}
+
+struct ChanceNode {
+    afterstate: Board,
+    visits: u64,
+    total_reward: f64,
+    // spawned board -> decision node idx
+    children: HashMap<Board, usize>,
+}
+
+struct Tree {
+    decision_nodes: Vec<DecisionNode>,
+    chance_nodes: Vec<ChanceNode>,
+    max_reward: f64,
+}
+
+impl Tree {
+    fn new(board: Board) -> Self {
+        Tree {
+            decision_nodes: vec![DecisionNode {
+                board,
+                visits: 0,
+                children: Vec::new(),
+                expanded: false,
+            }],
+            chance_nodes: Vec::new(),
+            max_reward: 0.,
+        }
+    }
+
+    fn add_decision_node(&mut self, board: Board) -> usize {
+        self.decision_nodes.push(DecisionNode {
+            board,
+            visits: 0,
+            children: Vec::new(),
+            expanded: false,
+        });
+        self.decision_nodes.len() - 1
+    }
+
+    fn expand<T: GameEngine>(&mut self, engine: &T, node_idx: usize) {
+        let board = self.decision_nodes[node_idx].board;
+        for direction in Move::iterator() {
+            let afterstate = engine.shift(board, direction);
+            if afterstate != board {
+                self.chance_nodes.push(ChanceNode {
+                    afterstate,
+                    visits: 0,
+                    total_reward: 0.,
+                    children: HashMap::new(),
+                });
+                let chance_idx = self.chance_nodes.len() - 1;
+                self.decision_nodes[node_idx]
+                    .children
+                    .push((direction, chance_idx));
+            }
+        }
+        self.decision_nodes[node_idx].expanded = true;
+    }
+
+    // Unvisited children are always tried first
+    fn select_uct(&self, node_idx: usize, exploration: f64) -> usize {
+        let node = &self.decision_nodes[node_idx];
+        let ln_visits = (node.visits.max(1) as f64).ln();
+        let max_reward = self.max_reward.max(1.);
+        let mut best_idx = node.children[0].1;
+        let mut best_value = f64::NEG_INFINITY;
+        for &(_, chance_idx) in &node.children {
+            let child = &self.chance_nodes[chance_idx];
+            if child.visits == 0 {
+                return chance_idx;
+            }
+            let mean = child.total_reward / child.visits as f64 / max_reward;
+            let value = mean + exploration * (ln_visits / child.visits as f64).sqrt();
+            if value > best_value {
+                best_value = value;
+                best_idx = chance_idx;
+            }
+        }
+        best_idx
+    }
+}
+
+impl<P: AI> Mcts<P> {
+    fn run_iteration<T: GameEngine>(&mut self, engine: &T, tree: &mut Tree) {
+        let mut decision_path = vec![0];
+        let mut chance_path = Vec::new();
+        let mut node_idx = 0;
+        let reward = loop {
+            let board = tree.decision_nodes[node_idx].board;
+            if !tree.decision_nodes[node_idx].expanded {
+                tree.expand(engine, node_idx);
+            }
+            if tree.decision_nodes[node_idx].children.is_empty() {
+                break engine.get_score(board) as f64;
+            }
+
+            let chance_idx = tree.select_uct(node_idx, self.config.exploration);
+            chance_path.push(chance_idx);
+            let is_new = tree.chance_nodes[chance_idx].visits == 0;
+            let afterstate = tree.chance_nodes[chance_idx].afterstate;
+            let spawned = engine::insert_random_tile_with_rng(afterstate, &mut self.rng);
+            node_idx = match tree.chance_nodes[chance_idx].children.get(&spawned) {
+                Some(&child_idx) => child_idx,
+                None => {
+                    let child_idx = tree.add_decision_node(spawned);
+                    tree.chance_nodes[chance_idx]
+                        .children
+                        .insert(spawned, child_idx);
+                    child_idx
+                }
+            };
+            decision_path.push(node_idx);
+
+            if is_new {
+                break self.rollout(engine, spawned) as f64;
+            }
+        };
+
+        tree.max_reward = tree.max_reward.max(reward);
+        for chance_idx in chance_path {
+            let node = &mut tree.chance_nodes[chance_idx];
+            node.visits += 1;
+            node.total_reward += reward;
+        }
+        for node_idx in decision_path {
+            tree.decision_nodes[node_idx].visits += 1;
+        }
+    }
+
+    fn rollout<T: GameEngine>(&mut self, engine: &T, board: Board) -> Score {
+        let (final_board, _) = play_out(
+            &mut self.rollout_policy,
+            engine,
+            board,
+            self.config.rollout_depth,
+        );
+        engine.get_score(final_board)
+    }
+
+    fn search<T: GameEngine>(&mut self, engine: &T, board: Board) -> Tree {
+        let mut tree = Tree::new(board);
+        match self.config.budget {
+            Budget::Iterations(iterations) => {
+                for _ in 0..iterations {
+                    self.run_iteration(engine, &mut tree);
+                }
+            }
+            Budget::Time(duration) => {
+                let start_time = Instant::now();
+                while start_time.elapsed() < duration {
+                    self.run_iteration(engine, &mut tree);
+                }
+            }
+        }
+        tree
+    }
+}
+
+impl<P: AI> AI for Mcts<P> {
+    fn get_next_move<T: GameEngine>(&mut self, engine: &T, board: Board) -> Option<Move> {
+        if engine.is_game_over(board) {
+            return None;
+        }
+        let tree = self.search(engine, board);
+        // play the most visited move
+        tree.decision_nodes[0]
+            .children
+            .iter()
+            .max_by_key(|&&(_, chance_idx)| tree.chance_nodes[chance_idx].visits)
+            .map(|&(direction, _)| direction)
+    }
+}
+
+impl<P: AI> ScoreMoves for Mcts<P> {
+    // The number of times each root move was visited by the search
+    fn score_moves<T: GameEngine>(&mut self, engine: &T, board: Board) -> MoveScores {
+        let mut move_scores = MoveScores::new();
+        if engine.is_game_over(board) {
+            return move_scores;
+        }
+        let tree = self.search(engine, board);
+        for &(direction, chance_idx) in &tree.decision_nodes[0].children {
+            move_scores.insert(direction, tree.chance_nodes[chance_idx].visits as f64);
+        }
+        move_scores
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::random::Random;
+    use crate::engine::GameEngineNoStores;
+
+    #[test]
+    fn it_mcts_move() {
+        let engine = GameEngineNoStores;
+        let mut mcts = Mcts::new(
+            MctsConfig {
+                budget: Budget::Iterations(50),
+                rollout_depth: Some(20),
+                ..MctsConfig::default()
+            },
+            Random,
+        );
+        // only up is possible
+        assert_eq!(
+            mcts.get_next_move(&engine, 0x0000000000001234),
+            Some(Move::Up)
+        );
+        assert!(mcts.get_next_move(&engine, 0x1200000000000000).is_some());
+        assert_eq!(mcts.get_next_move(&engine, 0x1234432112344321), None);
+        let move_scores = mcts.score_moves(&engine, 0x1200000000000000);
+        let total_visits = move_scores
+            .get_scores()
+            .iter()
+            .map(|&(_, visits)| visits)
+            .sum::<f64>();
+        assert_eq!(total_visits, 50.);
+    }
+}
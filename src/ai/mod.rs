@@ -1,13 +1,22 @@
 use crate::engine;
 use crate::engine::{Board, GameEngine, GameEngineStores, Move};
+use std::fmt;
 use std::fs::File;
 use std::io::Write;
 use std::time::SystemTime;
 
+pub mod adversarial;
+pub mod benchmark;
 pub mod default;
 pub mod expectimax;
 pub mod expectimax_old;
+pub mod hybrid;
+pub mod mcts;
+pub mod monte_carlo;
+pub mod noisy;
+pub mod ntuple;
 pub mod random;
+pub mod scenario;
 pub mod sequence;
 pub mod strategy;
 
@@ -15,15 +24,102 @@ pub trait AI {
     fn get_next_move<T: GameEngine>(&mut self, engine: &T, board: Board) -> Option<Move>;
 }
 
+// Agents that can say why they would choose a move on a board
+pub trait Explain {
+    type Explanation: fmt::Display;
+
+    fn explain<T: GameEngine>(&mut self, engine: &T, board: Board) -> Self::Explanation;
+}
+
+// A score for each move the agent would consider, higher is better. What the score means depends
+// on the agent, e.g. the expected value for expectimax or the visit count for MCTS.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MoveScores {
+    // in the order the agent considered the moves, ties are broken by this order
+    scores: Vec<(Move, f64)>,
+}
+
+impl MoveScores {
+    pub fn new() -> Self {
+        MoveScores { scores: Vec::new() }
+    }
+
+    pub fn insert(&mut self, direction: Move, score: f64) {
+        match self
+            .scores
+            .iter_mut()
+            .find(|(other, _)| *other == direction)
+        {
+            Some(entry) => entry.1 = score,
+            None => self.scores.push((direction, score)),
+        }
+    }
+
+    pub fn get(&self, direction: Move) -> Option<f64> {
+        self.scores
+            .iter()
+            .find(|(other, _)| *other == direction)
+            .map(|&(_, score)| score)
+    }
+
+    pub fn get_scores(&self) -> &Vec<(Move, f64)> {
+        &self.scores
+    }
+
+    pub fn best(&self) -> Option<Move> {
+        let mut best: Option<(Move, f64)> = None;
+        for &(direction, score) in &self.scores {
+            match best {
+                Some((_, best_score)) if score <= best_score => (),
+                _ => best = Some((direction, score)),
+            }
+        }
+        best.map(|(direction, _)| direction)
+    }
+
+    // Highest score first
+    pub fn ranked(&self) -> Vec<(Move, f64)> {
+        let mut ranked = self.scores.clone();
+        ranked.sort_by(|(_, a), (_, b)| b.partial_cmp(a).unwrap_or(std::cmp::Ordering::Equal));
+        ranked
+    }
+
+    // The scores divided by their total, only meaningful for non-negative scores such as visit
+    // counts
+    pub fn probabilities(&self) -> Vec<(Move, f64)> {
+        let total = self.scores.iter().map(|&(_, score)| score).sum::<f64>();
+        self.scores
+            .iter()
+            .map(|&(direction, score)| {
+                let probability = if total > 0. {
+                    score / total
+                } else {
+                    1. / self.scores.len() as f64
+                };
+                (direction, probability)
+            })
+            .collect()
+    }
+}
+
+// Agents that can score every move instead of only returning the best one
+pub trait ScoreMoves {
+    fn score_moves<T: GameEngine>(&mut self, engine: &T, board: Board) -> MoveScores;
+}
+
 pub trait AII {
     fn get_next_move(&self, board: Board) -> Option<crate::engine_unsafe::Move>;
 }
 
 pub fn run_ai<T: AI>(ai: &mut T) {
+    run_ai_from_board(ai, engine::new_board());
+}
+
+pub fn run_ai_from_board<T: AI>(ai: &mut T, board: Board) {
     let mut num_moves = 0;
     let start_time = SystemTime::now();
     let engine = GameEngineStores::new();
-    let mut board = engine::new_board();
+    let mut board = board;
     loop {
         println!("Score: {}", engine.get_score(board));
         println!("{}", engine::to_str(board));
@@ -42,15 +138,45 @@ pub fn run_ai<T: AI>(ai: &mut T) {
     };
     println!("Total number of moves made: {}", num_moves);
     println!("Total time taken: {}s", time_elapsed / 1000000000);
-    println!(
-        "Average move time for run was: {}ns, {}us, {}ms",
-        time_elapsed / num_moves,
-        time_elapsed / (num_moves * 1000),
-        time_elapsed / (num_moves * 1000000)
-    );
+    // None when the starting board was already lost
+    if let Some(average_time) = time_elapsed.checked_div(num_moves) {
+        println!(
+            "Average move time for run was: {}ns, {}us, {}ms",
+            average_time,
+            average_time / 1000,
+            average_time / 1000000
+        );
+    }
     println!("Final board: {}", engine::to_str(board));
 }
 
+// Lets the ai play from the given board until the game is over or the move limit is reached,
+// returns the final board and the number of moves made
+pub fn play_out<T: AI, E: GameEngine>(
+    ai: &mut T,
+    engine: &E,
+    board: Board,
+    max_moves: Option<usize>,
+) -> (Board, usize) {
+    let mut board = board;
+    let mut num_moves = 0;
+    loop {
+        if let Some(max_moves) = max_moves {
+            if num_moves >= max_moves {
+                break;
+            }
+        }
+        match ai.get_next_move(engine, board) {
+            Some(direction) => {
+                board = engine.make_move(board, direction);
+            }
+            None => break,
+        }
+        num_moves += 1;
+    }
+    (board, num_moves)
+}
+
 pub fn run_ai_with_delay<T: AI>(ai: &mut T, delay: u64) {
     let engine = GameEngineStores::new();
     let mut board = engine::new_board();
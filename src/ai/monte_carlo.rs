@@ -0,0 +1,112 @@
+use crate::ai::{play_out, MoveScores, ScoreMoves, AI};
+use crate::engine::{Board, GameEngine, Move};
+
+// Pure Monte Carlo search: every possible move is made a number of times and the game is played
+// out from the resulting board by the playout policy. The move with the best mean result is made.
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Objective {
+    // Mean score at the end of the playouts
+    Score,
+    // Mean number of moves made before the playouts ended
+    Survival,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MonteCarloConfig {
+    pub playouts: usize,
+    // Maximum number of moves in a playout, None plays until the game is over
+    pub playout_depth: Option<usize>,
+    pub objective: Objective,
+}
+
+impl Default for MonteCarloConfig {
+    fn default() -> Self {
+        MonteCarloConfig {
+            playouts: 100,
+            playout_depth: None,
+            objective: Objective::Score,
+        }
+    }
+}
+
+// Any AI can be used as the playout policy, e.g. Random or a Strategy
+pub struct MonteCarlo<P: AI> {
+    config: MonteCarloConfig,
+    playout_policy: P,
+}
+
+impl<P: AI> MonteCarlo<P> {
+    pub fn new(config: MonteCarloConfig, playout_policy: P) -> Self {
+        MonteCarlo {
+            config,
+            playout_policy,
+        }
+    }
+
+    fn evaluate_move<T: GameEngine>(&mut self, engine: &T, board: Board, direction: Move) -> f64 {
+        let total = (0..self.config.playouts).fold(0., |total, _| {
+            let new_board = engine.make_move(board, direction);
+            let (final_board, num_moves) = play_out(
+                &mut self.playout_policy,
+                engine,
+                new_board,
+                self.config.playout_depth,
+            );
+            match self.config.objective {
+                Objective::Score => total + engine.get_score(final_board) as f64,
+                Objective::Survival => total + num_moves as f64,
+            }
+        });
+        total / self.config.playouts as f64
+    }
+}
+
+impl<P: AI> AI for MonteCarlo<P> {
+    fn get_next_move<T: GameEngine>(&mut self, engine: &T, board: Board) -> Option<Move> {
+        self.score_moves(engine, board).best()
+    }
+}
+
+impl<P: AI> ScoreMoves for MonteCarlo<P> {
+    // The mean playout result of each possible move
+    fn score_moves<T: GameEngine>(&mut self, engine: &T, board: Board) -> MoveScores {
+        let mut move_scores = MoveScores::new();
+        for direction in Move::iterator() {
+            if engine.shift(board, direction) != board {
+                let score = self.evaluate_move(engine, board, direction);
+                move_scores.insert(direction, score);
+            }
+        }
+        move_scores
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::random::Random;
+    use crate::engine::GameEngineNoStores;
+
+    #[test]
+    fn it_monte_carlo_move() {
+        let engine = GameEngineNoStores;
+        let mut monte_carlo = MonteCarlo::new(
+            MonteCarloConfig {
+                playouts: 5,
+                playout_depth: Some(10),
+                objective: Objective::Survival,
+            },
+            Random,
+        );
+        // only up is possible
+        assert_eq!(
+            monte_carlo.get_next_move(&engine, 0x0000000000001234),
+            Some(Move::Up)
+        );
+        assert!(monte_carlo
+            .get_next_move(&engine, 0x1200000000000000)
+            .is_some());
+        assert_eq!(monte_carlo.get_next_move(&engine, 0x1234432112344321), None);
+    }
+}
@@ -0,0 +1,175 @@
+use crate::ai::{MoveScores, ScoreMoves, AI};
+use crate::engine;
+use crate::engine::{Board, GameEngine, Move};
+use rand::rngs::ThreadRng;
+use rand::{thread_rng, Rng};
+
+// Wraps an agent to model a player who misapplies it: with some probability the agent's move is
+// replaced by a mistake. The probability can change with the phase of the game, measured by the
+// largest tile on the board.
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Mistake {
+    // Any other legal move, picked uniformly
+    RandomLegalMove,
+    // The move the agent ranks second
+    SecondBest,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct NoiseConfig {
+    pub mistake: Mistake,
+    // (largest tile value, error rate) in increasing tile order, the rate of the last phase reached
+    // is used and there is no noise before the first phase
+    pub phase_rates: Vec<(u64, f64)>,
+}
+
+impl NoiseConfig {
+    pub fn constant(mistake: Mistake, error_rate: f64) -> Self {
+        NoiseConfig {
+            mistake,
+            phase_rates: vec![(0, error_rate)],
+        }
+    }
+
+    pub fn get_error_rate(&self, board: Board) -> f64 {
+        let largest_tile = engine::get_highest_tile_val(board);
+        self.phase_rates
+            .iter()
+            .take_while(|&&(tile, _)| tile <= largest_tile)
+            .last()
+            .map_or(0., |&(_, error_rate)| error_rate)
+    }
+}
+
+pub struct Noisy<A: AI + ScoreMoves> {
+    ai: A,
+    config: NoiseConfig,
+    rng: ThreadRng,
+    moves: u64,
+    mistakes: u64,
+}
+
+impl<A: AI + ScoreMoves> Noisy<A> {
+    pub fn new(ai: A, config: NoiseConfig) -> Self {
+        Noisy {
+            ai,
+            config,
+            rng: thread_rng(),
+            moves: 0,
+            mistakes: 0,
+        }
+    }
+
+    pub fn get_moves(&self) -> u64 {
+        self.moves
+    }
+
+    pub fn get_mistakes(&self) -> u64 {
+        self.mistakes
+    }
+
+    fn make_mistake<T: GameEngine>(
+        &mut self,
+        engine: &T,
+        board: Board,
+        intended_move: Move,
+    ) -> Option<Move> {
+        match self.config.mistake {
+            Mistake::RandomLegalMove => {
+                let other_moves = Move::iterator()
+                    .filter(|&direction| {
+                        direction != intended_move && engine.shift(board, direction) != board
+                    })
+                    .collect::<Vec<_>>();
+                if other_moves.is_empty() {
+                    None
+                } else {
+                    Some(other_moves[self.rng.gen_range(0, other_moves.len())])
+                }
+            }
+            Mistake::SecondBest => self
+                .ai
+                .score_moves(engine, board)
+                .ranked()
+                .into_iter()
+                .map(|(direction, _)| direction)
+                .find(|&direction| direction != intended_move),
+        }
+    }
+}
+
+impl<A: AI + ScoreMoves> AI for Noisy<A> {
+    fn get_next_move<T: GameEngine>(&mut self, engine: &T, board: Board) -> Option<Move> {
+        let intended_move = self.ai.get_next_move(engine, board)?;
+        self.moves += 1;
+        if self.rng.gen::<f64>() < self.config.get_error_rate(board) {
+            if let Some(mistake) = self.make_mistake(engine, board, intended_move) {
+                self.mistakes += 1;
+                return Some(mistake);
+            }
+        }
+        Some(intended_move)
+    }
+}
+
+impl<A: AI + ScoreMoves> ScoreMoves for Noisy<A> {
+    // The scores of the wrapped agent, the mistakes are random so they are not part of the scores
+    fn score_moves<T: GameEngine>(&mut self, engine: &T, board: Board) -> MoveScores {
+        self.ai.score_moves(engine, board)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::strategy::try_rules::TryMove;
+    use crate::ai::strategy::Strategy;
+    use crate::engine::GameEngineNoStores;
+
+    #[test]
+    fn it_phase_error_rate() {
+        let config = NoiseConfig {
+            mistake: Mistake::RandomLegalMove,
+            phase_rates: vec![(64, 0.1), (512, 0.3)],
+        };
+        assert_eq!(config.get_error_rate(0x1200000000000000), 0.);
+        assert_eq!(config.get_error_rate(0x1260000000000000), 0.1);
+        assert_eq!(config.get_error_rate(0x12a0000000000000), 0.3);
+    }
+
+    #[test]
+    fn it_noisy_always_wrong() {
+        let engine = GameEngineNoStores;
+        let strategy = Strategy::new(
+            &vec![],
+            &vec![TryMove::ProducesMerge(Move::Up)],
+            &vec![Move::Left, Move::Down, Move::Up, Move::Right],
+        )
+        .unwrap();
+        let board = 0x0000010000000000;
+        let mut second_best = Noisy::new(
+            strategy.clone(),
+            NoiseConfig::constant(Mistake::SecondBest, 1.),
+        );
+        assert_eq!(second_best.get_next_move(&engine, board), Some(Move::Down));
+        // the scores are the intended ones
+        assert_eq!(
+            second_best.score_moves(&engine, board),
+            strategy.clone().score_moves(&engine, board)
+        );
+        let mut random = Noisy::new(
+            strategy,
+            NoiseConfig::constant(Mistake::RandomLegalMove, 1.),
+        );
+        assert_ne!(random.get_next_move(&engine, board), Some(Move::Left));
+        assert_eq!(random.get_mistakes(), 1);
+        // only up is possible so no mistake can be made
+        assert_eq!(
+            random.get_next_move(&engine, 0x0000000000001234),
+            Some(Move::Up)
+        );
+        assert_eq!(random.get_moves(), 2);
+        assert_eq!(random.get_mistakes(), 1);
+    }
+}
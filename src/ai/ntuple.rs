@@ -0,0 +1,393 @@
+use crate::ai::expectimax::heuristic::Evaluator;
+use crate::ai::{MoveScores, ScoreMoves, AI};
+use crate::engine;
+use crate::engine::{Board, GameEngine, Move};
+use rand::thread_rng;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+// N-tuple network value function. Each tuple is a list of board positions (0 is the top left
+// tile, 15 the bottom right), the ranks of the tiles at those positions index a table of weights.
+// Every tuple is applied to the 8 rotations/reflections of the board, all sharing the same table,
+// and the value of a board is the sum of the looked up weights.
+//
+// The network values afterstates (boards after a move, before the tile spawns) and is trained
+// with TD(lambda) self-play, the agent plays greedily: the move maximising reward + V(afterstate).
+//
+// Weight file format, all integers are little endian:
+//  - magic bytes "NTUP"
+//  - u32 format version (1)
+//  - u32 number of tuples
+//  - for each tuple: u32 tuple length, then one u8 position per tile in the tuple
+//  - for each tuple, in order: 16^length f32 weights
+// The index of a weight is the sum of rank(position_i) * 16^i over the tuple.
+
+const MAGIC: &[u8; 4] = b"NTUP";
+const VERSION: u32 = 1;
+// 16^6 weights (64MB) per tuple, longer tuples need more memory than is practical
+const MAX_TUPLE_LEN: usize = 6;
+// bytes in the header before the tuples and the least bytes a tuple can take in the header
+const HEADER_LEN: u64 = 12;
+const MIN_TUPLE_HEADER_LEN: u64 = 5;
+
+pub struct NTupleNetwork {
+    tuples: Vec<Vec<usize>>,
+    // the 8 symmetric versions of each tuple
+    symmetries: Vec<Vec<Vec<usize>>>,
+    weights: Vec<Vec<f32>>,
+}
+
+// The 4 x 6-tuple network of Jaskowski, 2018. Needs 256MB of weights, use smaller tuples for quick
+// experiments.
+pub fn default_tuples() -> Vec<Vec<usize>> {
+    vec![
+        vec![0, 1, 2, 3, 4, 5],
+        vec![4, 5, 6, 7, 8, 9],
+        vec![0, 1, 2, 4, 5, 6],
+        vec![4, 5, 6, 8, 9, 10],
+    ]
+}
+
+impl NTupleNetwork {
+    // All weights start at zero
+    pub fn new(tuples: Vec<Vec<usize>>) -> Self {
+        for tuple in &tuples {
+            assert!(
+                !tuple.is_empty() && tuple.len() <= MAX_TUPLE_LEN,
+                "Tuples must have between 1 and {} positions",
+                MAX_TUPLE_LEN
+            );
+            assert!(
+                tuple.iter().all(|&pos| pos < 16),
+                "Tuple positions must be between 0 and 15"
+            );
+        }
+        let weights = tuples
+            .iter()
+            .map(|tuple| vec![0.; 1 << (4 * tuple.len())])
+            .collect();
+        let symmetries = tuples.iter().map(|tuple| get_symmetries(tuple)).collect();
+        NTupleNetwork {
+            tuples,
+            symmetries,
+            weights,
+        }
+    }
+
+    pub fn get_tuples(&self) -> &Vec<Vec<usize>> {
+        &self.tuples
+    }
+
+    pub fn evaluate(&self, board: Board) -> f32 {
+        let mut value = 0.;
+        for (symmetries, weights) in self.symmetries.iter().zip(self.weights.iter()) {
+            for positions in symmetries {
+                value += weights[get_index(board, positions)];
+            }
+        }
+        value
+    }
+
+    // Adds delta to every weight used to evaluate the board
+    pub fn update(&mut self, board: Board, delta: f32) {
+        for (symmetries, weights) in self.symmetries.iter().zip(self.weights.iter_mut()) {
+            for positions in symmetries {
+                weights[get_index(board, positions)] += delta;
+            }
+        }
+    }
+
+    // Returns the move with the highest reward + value of the afterstate, with the afterstate and
+    // reward, None if no move changes the board
+    fn best_move<T: GameEngine>(&self, engine: &T, board: Board) -> Option<(Move, Board, f32)> {
+        let score = engine.get_score(board);
+        let mut best: Option<(Move, Board, f32)> = None;
+        let mut best_value = f32::NEG_INFINITY;
+        for direction in Move::iterator() {
+            let afterstate = engine.shift(board, direction);
+            if afterstate == board {
+                continue;
+            }
+            let reward = (engine.get_score(afterstate) - score) as f32;
+            let value = reward + self.evaluate(afterstate);
+            if value > best_value {
+                best_value = value;
+                best = Some((direction, afterstate, reward));
+            }
+        }
+        best
+    }
+
+    pub fn save(&self, filename: &str) -> io::Result<()> {
+        let mut f = BufWriter::new(File::create(Path::new(filename))?);
+        f.write_all(MAGIC)?;
+        f.write_all(&VERSION.to_le_bytes())?;
+        f.write_all(&(self.tuples.len() as u32).to_le_bytes())?;
+        for tuple in &self.tuples {
+            f.write_all(&(tuple.len() as u32).to_le_bytes())?;
+            for &pos in tuple {
+                f.write_all(&[pos as u8])?;
+            }
+        }
+        for weights in &self.weights {
+            for weight in weights {
+                f.write_all(&weight.to_le_bytes())?;
+            }
+        }
+        f.flush()
+    }
+
+    pub fn load(filename: &str) -> io::Result<Self> {
+        let file = File::open(Path::new(filename))?;
+        let file_len = file.metadata()?.len();
+        let mut f = BufReader::new(file);
+        let invalid_data = |msg: &str| io::Error::new(io::ErrorKind::InvalidData, msg.to_string());
+
+        let mut magic = [0; 4];
+        f.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(invalid_data("not an n-tuple weight file"));
+        }
+        if read_u32(&mut f)? != VERSION {
+            return Err(invalid_data("unsupported n-tuple weight file version"));
+        }
+        let num_tuples = read_u32(&mut f)? as usize;
+        // the sizes are checked against the file before anything is allocated
+        if HEADER_LEN + num_tuples as u64 * MIN_TUPLE_HEADER_LEN > file_len {
+            return Err(invalid_data("too many tuples for the file length"));
+        }
+        let mut tuples: Vec<Vec<usize>> = Vec::with_capacity(num_tuples);
+        for _ in 0..num_tuples {
+            let len = read_u32(&mut f)? as usize;
+            if len == 0 || len > MAX_TUPLE_LEN {
+                return Err(invalid_data("invalid tuple length"));
+            }
+            let mut positions = vec![0; len];
+            f.read_exact(&mut positions)?;
+            if positions.iter().any(|&pos| pos >= 16) {
+                return Err(invalid_data("invalid tuple position"));
+            }
+            tuples.push(positions.iter().map(|&pos| pos as usize).collect());
+        }
+        let expected_len = tuples.iter().fold(HEADER_LEN, |len, tuple| {
+            len + 4 + tuple.len() as u64 + 4 * (1 << (4 * tuple.len()))
+        });
+        if expected_len != file_len {
+            return Err(invalid_data("the file length does not match the tuples"));
+        }
+
+        let mut network = NTupleNetwork::new(tuples);
+        let mut bytes = [0; 4];
+        for weights in network.weights.iter_mut() {
+            for weight in weights.iter_mut() {
+                f.read_exact(&mut bytes)?;
+                *weight = f32::from_le_bytes(bytes);
+            }
+        }
+        Ok(network)
+    }
+}
+
+impl AI for NTupleNetwork {
+    fn get_next_move<T: GameEngine>(&mut self, engine: &T, board: Board) -> Option<Move> {
+        self.best_move(engine, board)
+            .map(|(direction, _, _)| direction)
+    }
+}
+
+impl ScoreMoves for NTupleNetwork {
+    // The reward of each possible move plus the value of its afterstate
+    fn score_moves<T: GameEngine>(&mut self, engine: &T, board: Board) -> MoveScores {
+        let score = engine.get_score(board);
+        let mut move_scores = MoveScores::new();
+        for direction in Move::iterator() {
+            let afterstate = engine.shift(board, direction);
+            if afterstate != board {
+                let reward = (engine.get_score(afterstate) - score) as f64;
+                move_scores.insert(
+                    direction,
+                    reward + NTupleNetwork::evaluate(self, afterstate) as f64,
+                );
+            }
+        }
+        move_scores
+    }
+}
+
+// The network values afterstates, which are the chance nodes at the leaves of expectimax
+impl Evaluator for NTupleNetwork {
+    fn evaluate(&self, board: Board) -> f64 {
+        NTupleNetwork::evaluate(self, board) as f64
+    }
+
+    fn uses_rewards(&self) -> bool {
+        true
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct TrainConfig {
+    pub episodes: usize,
+    pub learning_rate: f32,
+    // 0 gives TD(0), values closer to 1 look further ahead in the episode
+    pub lambda: f32,
+    // Print the average score every this many episodes
+    pub report_every: usize,
+}
+
+impl Default for TrainConfig {
+    fn default() -> Self {
+        TrainConfig {
+            episodes: 100000,
+            learning_rate: 0.0025,
+            lambda: 0.,
+            report_every: 1000,
+        }
+    }
+}
+
+// Trains the network with afterstate TD(lambda) self-play. The lambda returns are computed
+// backwards from the end of each episode: G_t = r_t+1 + (1 - lambda) * V(s_t+1) + lambda * G_t+1,
+// the final afterstate has a return of 0. Each afterstate is updated towards its return as it is
+// reached, so V(s_t+1) already includes the update from this episode.
+pub fn train<T: GameEngine>(engine: &T, network: &mut NTupleNetwork, config: TrainConfig) {
+    println!("Starting n-tuple network training...");
+    let mut rng = thread_rng();
+    let mut total_score = 0;
+    let mut max_score = 0;
+    for episode in 1..=config.episodes {
+        // (afterstate, reward for the move into it)
+        let mut trajectory = Vec::new();
+        let mut board = engine::new_board();
+        while let Some((_, afterstate, reward)) = network.best_move(engine, board) {
+            trajectory.push((afterstate, reward));
+            board = engine::insert_random_tile_with_rng(afterstate, &mut rng);
+        }
+
+        let mut lambda_return = 0.;
+        let mut next: Option<(Board, f32)> = None;
+        for &(afterstate, reward) in trajectory.iter().rev() {
+            if let Some((next_afterstate, next_reward)) = next {
+                lambda_return = next_reward
+                    + (1. - config.lambda) * network.evaluate(next_afterstate)
+                    + config.lambda * lambda_return;
+            }
+            let error = lambda_return - network.evaluate(afterstate);
+            network.update(afterstate, config.learning_rate * error);
+            next = Some((afterstate, reward));
+        }
+
+        let score = engine.get_score(board);
+        total_score += score;
+        max_score = max_score.max(score);
+        if config.report_every > 0 && episode % config.report_every == 0 {
+            println!(
+                "Episode {}/{}: average score {}, max score {}",
+                episode,
+                config.episodes,
+                total_score / config.report_every as u64,
+                max_score
+            );
+            total_score = 0;
+            max_score = 0;
+        }
+    }
+}
+
+fn get_index(board: Board, positions: &[usize]) -> usize {
+    positions.iter().enumerate().fold(0, |index, (i, &pos)| {
+        index | ((engine::get_tile(board, pos) as usize) << (4 * i))
+    })
+}
+
+// The positions of the tuple on the 4 rotations of the board and their mirror images
+fn get_symmetries(tuple: &[usize]) -> Vec<Vec<usize>> {
+    let rotate = |pos: usize| {
+        let (row, col) = (pos / 4, pos % 4);
+        col * 4 + (3 - row)
+    };
+    let mirror = |pos: usize| {
+        let (row, col) = (pos / 4, pos % 4);
+        row * 4 + (3 - col)
+    };
+    let mut symmetries = Vec::new();
+    let mut positions = tuple.to_vec();
+    for _ in 0..4 {
+        symmetries.push(positions.iter().map(|&pos| mirror(pos)).collect());
+        symmetries.push(positions.clone());
+        positions = positions.iter().map(|&pos| rotate(pos)).collect();
+    }
+    symmetries
+}
+
+fn read_u32<R: Read>(f: &mut R) -> io::Result<u32> {
+    let mut bytes = [0; 4];
+    f.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::GameEngineNoStores;
+
+    fn small_tuples() -> Vec<Vec<usize>> {
+        vec![vec![0, 1, 2, 3], vec![4, 5, 6, 7]]
+    }
+
+    #[test]
+    fn it_ntuple_symmetric_evaluation() {
+        let mut network = NTupleNetwork::new(small_tuples());
+        let board = 0x1200000000000000;
+        network.update(board, 1.);
+        // every symmetric board uses the same weights
+        assert_eq!(
+            network.evaluate(board),
+            network.evaluate(0x0000000000000021)
+        );
+        assert_eq!(
+            network.evaluate(board),
+            network.evaluate(engine::transpose(board))
+        );
+    }
+
+    #[test]
+    fn it_ntuple_train_save_load() {
+        let engine = GameEngineNoStores;
+        let mut network = NTupleNetwork::new(small_tuples());
+        train(
+            &engine,
+            &mut network,
+            TrainConfig {
+                episodes: 5,
+                lambda: 0.5,
+                report_every: 0,
+                ..TrainConfig::default()
+            },
+        );
+        let board = 0x1200000000000000;
+        let path = std::env::temp_dir().join("msc_2048_ai_ntuple_weights.bin");
+        let filename = path.to_str().unwrap();
+        network.save(filename).unwrap();
+        let mut loaded = NTupleNetwork::load(filename).unwrap();
+        std::fs::remove_file(path).unwrap();
+        assert_eq!(loaded.get_tuples(), network.get_tuples());
+        assert_eq!(loaded.evaluate(board), network.evaluate(board));
+        assert!(loaded.get_next_move(&engine, board).is_some());
+        assert_eq!(loaded.get_next_move(&engine, 0x1234432112344321), None);
+
+        // a header asking for more weights than the file holds is rejected before allocating
+        let mut header = Vec::new();
+        header.extend_from_slice(MAGIC);
+        header.extend_from_slice(&VERSION.to_le_bytes());
+        header.extend_from_slice(&1_u32.to_le_bytes());
+        header.extend_from_slice(&6_u32.to_le_bytes());
+        header.extend_from_slice(&[0, 1, 2, 3, 4, 5]);
+        let path = std::env::temp_dir().join("msc_2048_ai_ntuple_truncated.bin");
+        std::fs::write(&path, &header).unwrap();
+        let error = NTupleNetwork::load(path.to_str().unwrap()).err().unwrap();
+        std::fs::remove_file(path).unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+}
@@ -1,4 +1,4 @@
-use crate::ai::AI;
+use crate::ai::{MoveScores, ScoreMoves, AI};
 use crate::engine::Board;
 use crate::engine::GameEngine;
 use crate::engine::Move;
@@ -22,3 +22,16 @@ impl AI for Random {
         }
     }
 }
+
+impl ScoreMoves for Random {
+    // Every possible move is equally likely
+    fn score_moves<T: GameEngine>(&mut self, engine: &T, board: Board) -> MoveScores {
+        let mut move_scores = MoveScores::new();
+        for direction in Move::iterator() {
+            if engine.shift(board, direction) != board {
+                move_scores.insert(direction, 1.);
+            }
+        }
+        move_scores
+    }
+}
@@ -0,0 +1,168 @@
+use crate::ai::strategy::search::median;
+use crate::ai::{play_out, AI};
+use crate::engine::{Board, GameEngine, Score};
+use std::fmt;
+use std::fs;
+use std::io;
+
+// A scenario suite is a text file of named starting positions, one per line as
+// "name,hexboard" where the board is the 16 tile ranks in hex, top left tile first:
+//
+//   # the largest tile has left the corner
+//   broken_corner,00001000234059a8
+//
+// Lines starting with # and blank lines are ignored. Every agent plays each scenario a number of
+// times so endgame skill can be measured without playing the start of every game.
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Scenario {
+    pub name: String,
+    pub board: Board,
+}
+
+pub fn load_scenarios(filename: &str) -> io::Result<Vec<Scenario>> {
+    parse_scenarios(&fs::read_to_string(filename)?)
+}
+
+pub fn parse_scenarios(contents: &str) -> io::Result<Vec<Scenario>> {
+    let mut scenarios = Vec::new();
+    for (line_idx, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let invalid_line = || {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("invalid scenario on line {}: {}", line_idx + 1, line),
+            )
+        };
+        let mut parts = line.splitn(2, ',');
+        let name = parts.next().ok_or_else(invalid_line)?.trim();
+        let board = parts.next().ok_or_else(invalid_line)?.trim();
+        if name.is_empty() || board.len() != 16 {
+            return Err(invalid_line());
+        }
+        let board = u64::from_str_radix(board, 16).map_err(|_| invalid_line())?;
+        scenarios.push(Scenario {
+            name: name.to_string(),
+            board,
+        });
+    }
+    Ok(scenarios)
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ScenarioResult {
+    pub name: String,
+    pub scores: Vec<Score>,
+    // number of moves made in each run
+    pub moves: Vec<usize>,
+    // a run survives if it makes at least this many moves
+    pub survival_moves: usize,
+}
+
+impl ScenarioResult {
+    pub fn get_survival_rate(&self) -> f64 {
+        let survived = self
+            .moves
+            .iter()
+            .filter(|&&num_moves| num_moves >= self.survival_moves)
+            .count();
+        survived as f64 / self.moves.len() as f64
+    }
+}
+
+impl fmt::Display for ScenarioResult {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let average = self.scores.iter().sum::<Score>() as f64 / self.scores.len() as f64;
+        write!(
+            f,
+            "{}: median {}, average {:.1}, min {}, max {}, survived {} moves in {:.1}% of runs",
+            self.name,
+            median(&self.scores),
+            average,
+            self.scores.iter().min().unwrap_or(&0),
+            self.scores.iter().max().unwrap_or(&0),
+            self.survival_moves,
+            self.get_survival_rate() * 100.
+        )
+    }
+}
+
+pub fn run_scenario<A: AI, E: GameEngine>(
+    ai: &mut A,
+    engine: &E,
+    scenario: &Scenario,
+    runs: usize,
+    survival_moves: usize,
+) -> ScenarioResult {
+    assert!(runs > 0, "each scenario needs at least one run");
+    let (scores, moves) = (0..runs)
+        .map(|_| {
+            let (board, num_moves) = play_out(ai, engine, scenario.board, None);
+            (engine.get_score(board), num_moves)
+        })
+        .unzip();
+    ScenarioResult {
+        name: scenario.name.clone(),
+        scores,
+        moves,
+        survival_moves,
+    }
+}
+
+pub fn run_scenario_suite<A: AI, E: GameEngine>(
+    ai: &mut A,
+    engine: &E,
+    scenarios: &[Scenario],
+    runs: usize,
+    survival_moves: usize,
+) -> Vec<ScenarioResult> {
+    scenarios
+        .iter()
+        .map(|scenario| {
+            let result = run_scenario(ai, engine, scenario, runs, survival_moves);
+            println!("{}", result);
+            result
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::random::Random;
+    use crate::engine::GameEngineNoStores;
+
+    #[test]
+    fn it_parse_scenarios() {
+        let engine = GameEngineNoStores;
+        let scenarios = parse_scenarios(include_str!("../../scenarios/endgames.txt")).unwrap();
+        assert_eq!(scenarios.len(), 4);
+        assert!(scenarios
+            .iter()
+            .all(|scenario| !engine.is_game_over(scenario.board)));
+        assert_eq!(
+            parse_scenarios("# comment\n\nlost, 1234432112344321\n").unwrap(),
+            vec![Scenario {
+                name: "lost".to_string(),
+                board: 0x1234432112344321
+            }]
+        );
+        assert!(parse_scenarios("no_board").is_err());
+        assert!(parse_scenarios("short,1234").is_err());
+    }
+
+    #[test]
+    fn it_run_scenario() {
+        let engine = GameEngineNoStores;
+        let scenario = Scenario {
+            name: "lost".to_string(),
+            board: 0x1234432112344321,
+        };
+        let result = run_scenario(&mut Random, &engine, &scenario, 3, 1);
+        assert_eq!(result.scores, vec![engine.get_score(scenario.board); 3]);
+        assert_eq!(result.get_survival_rate(), 0.);
+    }
+}
@@ -1,11 +1,11 @@
 use std::fs::File;
 use std::io::prelude::Write;
 
-use crate::ai::AI;
+use crate::ai::{play_out, AI};
 use crate::engine;
 use crate::engine::{Board, GameEngine, GameEngineStores, Move, Score};
 
-type MoveSequence = Vec<Move>;
+pub type MoveSequence = Vec<Move>;
 
 pub struct Sequence {
     move_sequence: MoveSequence,
@@ -105,14 +105,14 @@ fn get_average_score<T: GameEngine>(sequence: MoveSequence, engine: &T, runs: u3
 }
 
 fn run_sequence<T: GameEngine>(sequence: MoveSequence, engine: &T) -> Score {
-    let mut board = engine::new_board();
-    let mut sequence_ai = Sequence::new(sequence);
-    loop {
-        let next_move = sequence_ai.get_next_move(engine, board);
-        match next_move {
-            Some(move_dir) => board = engine.make_move(board, move_dir),
-            None => break,
-        }
-    }
+    run_sequence_from_board(sequence, engine, engine::new_board())
+}
+
+pub fn run_sequence_from_board<T: GameEngine>(
+    sequence: MoveSequence,
+    engine: &T,
+    board: Board,
+) -> Score {
+    let (board, _) = play_out(&mut Sequence::new(sequence), engine, board, None);
     engine.get_score(board)
 }
@@ -1,3 +1,4 @@
+use crate::engine;
 use crate::engine::Board;
 use crate::engine::Move;
 use crate::engine::{get_tile, GameEngine};
@@ -98,6 +99,17 @@ impl Corner {
             Corner::TopRight => 3,
         }
     }
+
+    pub fn iterator() -> impl Iterator<Item = Corner> {
+        [
+            Corner::BottomLeft,
+            Corner::TopLeft,
+            Corner::BottomRight,
+            Corner::TopRight,
+        ]
+        .iter()
+        .copied()
+    }
 }
 
 impl fmt::Display for Corner {
@@ -111,6 +123,30 @@ impl fmt::Display for Corner {
     }
 }
 
+// How the chance of the spawn is handled when looking past the move
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SpawnCase {
+    // one spawn is enough
+    Best,
+    // it must hold for every spawn
+    Worst,
+}
+
+impl SpawnCase {
+    pub fn iterator() -> impl Iterator<Item = SpawnCase> {
+        [SpawnCase::Best, SpawnCase::Worst].iter().copied()
+    }
+}
+
+impl fmt::Display for SpawnCase {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SpawnCase::Best => write!(f, "best case"),
+            SpawnCase::Worst => write!(f, "worst case"),
+        }
+    }
+}
+
 pub fn is_move_possible<T: GameEngine>(engine: &T, board: Board, direction: Move) -> bool {
     let new_board = engine.shift(board, direction);
     board != new_board
@@ -383,6 +419,190 @@ pub fn is_row_empty(board: Board, row: Row) -> bool {
     false
 }
 
+pub fn count_empty_tiles(board: Board) -> u64 {
+    engine::count_empty(board)
+}
+
+// Number of different tile values on the board, empty tiles are not counted
+pub fn count_unique_tiles(board: Board) -> u64 {
+    let mut bitset: u64 = 0;
+    for idx in 0..16 {
+        bitset |= 1 << get_tile(board, idx);
+    }
+    (bitset >> 1).count_ones() as u64
+}
+
+pub fn count_tiles_at_least(board: Board, tile_value: u64) -> u64 {
+    (0..16)
+        .map(|idx| get_tile(board, idx))
+        .filter(|&tile| tile != 0 && 2_u64.pow(tile as u32) >= tile_value)
+        .count() as u64
+}
+
+// The rows (up and down) or columns (left and right) are ordered by the sum of their tile values,
+// with the largest sum at the edge the direction points to
+pub fn is_sum_ordered(board: Board, direction: Move) -> bool {
+    let tile_value = |idx| match get_tile(board, idx) {
+        0 => 0,
+        tile => 2_u64.pow(tile as u32),
+    };
+    let sums = (0..4)
+        .map(|line_idx| {
+            (0..4)
+                .map(|i| match direction {
+                    Move::Up | Move::Down => tile_value(line_idx * 4 + i),
+                    Move::Left | Move::Right => tile_value(i * 4 + line_idx),
+                })
+                .sum::<u64>()
+        })
+        .collect::<Vec<_>>();
+    match direction {
+        Move::Up | Move::Left => sums.windows(2).all(|pair| pair[0] >= pair[1]),
+        Move::Down | Move::Right => sums.windows(2).all(|pair| pair[0] <= pair[1]),
+    }
+}
+
+// The order tiles are visited in a snake from the corner: along the corner's row, then back along
+// the next row and so on
+fn snake_path(corner: Corner) -> Vec<usize> {
+    let (rows, starts_left) = match corner {
+        Corner::TopLeft => ([0, 1, 2, 3], true),
+        Corner::TopRight => ([0, 1, 2, 3], false),
+        Corner::BottomLeft => ([3, 2, 1, 0], true),
+        Corner::BottomRight => ([3, 2, 1, 0], false),
+    };
+    let mut path = Vec::new();
+    for (i, &row) in rows.iter().enumerate() {
+        let left_to_right = (i % 2 == 0) == starts_left;
+        for j in 0..4 {
+            let col = if left_to_right { j } else { 3 - j };
+            path.push(row * 4 + col);
+        }
+    }
+    path
+}
+
+// Checks the boards after the move and the spawn, false if the move is not possible
+pub fn holds_after_spawn<T: GameEngine>(
+    engine: &T,
+    board: Board,
+    direction: Move,
+    spawn_case: SpawnCase,
+    check: impl Fn(Board) -> bool,
+) -> bool {
+    let new_board = engine.shift(board, direction);
+    if new_board == board {
+        return false;
+    }
+    let mut spawns = engine::get_spawns(new_board).into_iter();
+    match spawn_case {
+        SpawnCase::Best => spawns.any(|(spawned, _)| check(spawned)),
+        SpawnCase::Worst => spawns.all(|(spawned, _)| check(spawned)),
+    }
+}
+
+pub fn is_any_merge_possible(board: Board) -> bool {
+    is_merge_possible(board, Move::Left) || is_merge_possible(board, Move::Up)
+}
+
+// True when there are no possible moves as well
+pub fn does_every_move_lose_corner<T: GameEngine>(
+    engine: &T,
+    board: Board,
+    corner: Corner,
+) -> bool {
+    Move::iterator()
+        .map(|direction| engine.shift(board, direction))
+        .filter(|&new_board| new_board != board)
+        .all(|new_board| !is_largest_tile_in_corner(new_board, corner))
+}
+
+// Chance that the tile spawned after the move lands on the corner, 0 if the move is not possible
+pub fn get_corner_spawn_probability<T: GameEngine>(
+    engine: &T,
+    board: Board,
+    direction: Move,
+    corner: Corner,
+) -> f64 {
+    let new_board = engine.shift(board, direction);
+    if new_board == board || get_tile(new_board, corner.get_idx()) != 0 {
+        return 0.;
+    }
+    engine::get_spawns(new_board)
+        .iter()
+        .filter(|&&(spawned, _)| get_tile(spawned, corner.get_idx()) != 0)
+        .map(|&(_, probability)| probability)
+        .sum()
+}
+
+// Chance that no move is possible after the move and the spawn
+pub fn get_game_over_probability<T: GameEngine>(engine: &T, board: Board, direction: Move) -> f64 {
+    let new_board = engine.shift(board, direction);
+    if new_board == board {
+        return 0.;
+    }
+    engine::get_spawns(new_board)
+        .iter()
+        .filter(|&&(spawned, _)| engine.is_game_over(spawned))
+        .map(|&(_, probability)| probability)
+        .sum()
+}
+
+// The tiles at the start of the snake path that never increase, ending at the first empty tile
+fn snake_chain(board: Board, corner: Corner) -> Vec<u64> {
+    let mut chain: Vec<u64> = Vec::new();
+    for idx in snake_path(corner) {
+        let tile = get_tile(board, idx);
+        if tile == 0 || chain.last().is_some_and(|&previous| tile > previous) {
+            break;
+        }
+        chain.push(tile);
+    }
+    chain
+}
+
+pub fn snake_chain_length(board: Board, corner: Corner) -> usize {
+    snake_chain(board, corner).len()
+}
+
+// Merges inside the chain keep the sum, it only drops when a tile leaves the chain
+pub fn snake_chain_sum(board: Board, corner: Corner) -> u64 {
+    snake_chain(board, corner)
+        .iter()
+        .map(|&tile| 2_u64.pow(tile as u32))
+        .sum()
+}
+
+// How closely the board follows a gradient falling away from the corner, 1 when the tile ranks
+// are placed in the same order as the gradient weights
+pub fn gradient_conformity(board: Board, corner: Corner) -> f64 {
+    let corner_idx = corner.get_idx();
+    let (corner_row, corner_col) = (corner_idx / 4, corner_idx % 4);
+    let mut weights = (0..16)
+        .map(|idx: usize| {
+            let distance = (idx / 4).abs_diff(corner_row) + (idx % 4).abs_diff(corner_col);
+            (6 - distance) as u64
+        })
+        .collect::<Vec<_>>();
+    let mut tiles = (0..16).map(|idx| get_tile(board, idx)).collect::<Vec<_>>();
+    let score = weights
+        .iter()
+        .zip(&tiles)
+        .map(|(weight, tile)| weight * tile)
+        .sum::<u64>();
+    weights.sort_unstable();
+    tiles.sort_unstable();
+    let best_score = weights
+        .iter()
+        .zip(&tiles)
+        .map(|(weight, tile)| weight * tile)
+        .sum::<u64>();
+    if best_score == 0 {
+        return 1.;
+    }
+    score as f64 / best_score as f64
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -397,6 +617,84 @@ mod tests {
         assert_eq!(are_2_largest_tiles_adjacent(0x3333000022220000), false);
     }
 
+    #[test]
+    fn it_count_tiles() {
+        let board = 0x1134000000000520;
+        assert_eq!(count_empty_tiles(board), 10);
+        assert_eq!(count_unique_tiles(board), 5);
+        assert_eq!(count_tiles_at_least(board, 8), 3);
+        assert_eq!(count_tiles_at_least(board, 2), 6);
+        assert_eq!(count_tiles_at_least(board, 64), 0);
+    }
+
+    #[test]
+    fn it_is_sum_ordered() {
+        let board = 0x0000000110005321;
+        assert_eq!(is_sum_ordered(board, Move::Down), true);
+        assert_eq!(is_sum_ordered(board, Move::Up), false);
+        assert_eq!(is_sum_ordered(board, Move::Left), true);
+        assert_eq!(is_sum_ordered(board, Move::Right), false);
+    }
+
+    #[test]
+    fn it_spawn_probabilities() {
+        let engine = GameEngineNoStores;
+        // moving up leaves 13 empty tiles including the bottom left corner
+        let board = 0x0000000000001230;
+        let probability =
+            get_corner_spawn_probability(&engine, board, Move::Up, Corner::BottomLeft);
+        assert!((probability - 1. / 13.).abs() < 1e-9);
+        assert_eq!(
+            get_corner_spawn_probability(&engine, board, Move::Left, Corner::BottomLeft),
+            0.
+        );
+        // one empty tile is left after the move, a 2 spawning there ends the game but a 4 can merge
+        let board = 0x1234432112340432;
+        let probability = get_game_over_probability(&engine, board, Move::Left);
+        assert!((probability - 0.9).abs() < 1e-9);
+        assert_eq!(
+            get_game_over_probability(&engine, 0x1234432112344320, Move::Left),
+            0.
+        );
+    }
+
+    #[test]
+    fn it_snake_chain() {
+        assert_eq!(
+            snake_chain_length(0x0000000000000123, Corner::BottomLeft),
+            0
+        );
+        // 7 6 5 4 along the bottom row, then 3 2 back along the next
+        let board = 0x0000000000237654;
+        assert_eq!(snake_chain_length(board, Corner::BottomLeft), 6);
+        assert_eq!(
+            snake_chain_sum(board, Corner::BottomLeft),
+            128 + 64 + 32 + 16 + 8 + 4
+        );
+        assert_eq!(snake_chain_length(board, Corner::BottomRight), 1);
+        assert_eq!(
+            snake_chain_length(0x0000000000327654, Corner::BottomLeft),
+            5
+        );
+    }
+
+    #[test]
+    fn it_gradient_conformity() {
+        assert_eq!(gradient_conformity(0, Corner::BottomLeft), 1.);
+        assert_eq!(
+            gradient_conformity(0x0000000000001000, Corner::BottomLeft),
+            1.
+        );
+        assert_eq!(
+            gradient_conformity(0x0001000000000000, Corner::BottomLeft),
+            0.
+        );
+        assert!(
+            gradient_conformity(0x0000000110002321, Corner::BottomLeft)
+                > gradient_conformity(0x0000000110002321, Corner::TopRight)
+        );
+    }
+
     #[test]
     fn it_is_left_column_monotonic() {
         assert_eq!(is_column_monotonic(0x1234123412341234, Column::Left), true);
@@ -2,6 +2,8 @@ use super::attributes;
 use super::attributes::Column;
 use super::attributes::Corner;
 use super::attributes::Row;
+use super::attributes::SpawnCase;
+use super::condition::{Condition, MAX_TERMS};
 use crate::engine::Board;
 use crate::engine::GameEngine;
 use crate::engine::Move;
@@ -23,6 +25,19 @@ pub enum BanMove {
     MovesLargestTileOutOfCorner(Move, Corner),
     FillsColumn(Move, Column),
     FillsRow(Move, Row),
+    BreaksSnakeChain(Move, Corner),
+    // looks at the board after the move and the spawn
+    EveryNextMoveLosesCorner(Move, Corner, SpawnCase),
+    // looks at where the next tile can spawn
+    IfSpawnCanLandInCorner(Move, Corner),
+    // the chance that the move is followed by a spawn that leaves no moves, as a percentage
+    IfGameOverChanceAbove(Move, u64),
+    // thresholds on numeric attributes
+    IfEmptyTilesAtLeast(Move, u64),
+    IfUniqueTilesAtMost(Move, u64),
+    BreaksSumOrder(Move, Move),
+    // The move is banned when the condition holds
+    When(Move, Condition),
 }
 
 impl BanMove {
@@ -62,6 +77,36 @@ impl BanMove {
             BanMove::FillsRow(direction, row) => {
                 ban_move_if_fills_row(engine, board, *direction, *row)
             }
+            BanMove::BreaksSnakeChain(direction, corner) => {
+                ban_move_if_breaks_snake_chain(engine, board, *direction, *corner)
+            }
+            BanMove::EveryNextMoveLosesCorner(direction, corner, spawn_case) => {
+                ban_move_if_every_next_move_loses_corner(
+                    engine,
+                    board,
+                    *direction,
+                    *corner,
+                    *spawn_case,
+                )
+            }
+            BanMove::IfSpawnCanLandInCorner(direction, corner) => {
+                ban_move_if_spawn_can_land_in_corner(engine, board, *direction, *corner)
+            }
+            BanMove::IfGameOverChanceAbove(direction, percentage) => {
+                ban_move_if_game_over_chance_above(engine, board, *direction, *percentage)
+            }
+            BanMove::IfEmptyTilesAtLeast(direction, threshold) => {
+                ban_move_if_empty_tiles_at_least(board, *direction, *threshold)
+            }
+            BanMove::IfUniqueTilesAtMost(direction, threshold) => {
+                ban_move_if_unique_tiles_at_most(board, *direction, *threshold)
+            }
+            BanMove::BreaksSumOrder(direction, towards) => {
+                ban_move_if_breaks_sum_order(engine, board, *direction, *towards)
+            }
+            BanMove::When(direction, condition) => {
+                ban_move_when(engine, board, *direction, *condition)
+            }
         }
     }
 
@@ -79,11 +124,45 @@ impl BanMove {
         variations.append(&mut ban_move_if_moves_largest_tile_out_of_corner_variations());
         variations.append(&mut ban_move_if_fills_column_variations());
         variations.append(&mut ban_move_if_fills_row_variations());
+        variations.append(&mut ban_move_if_breaks_snake_chain_variations());
+        variations.append(&mut ban_move_if_every_next_move_loses_corner_variations());
+        variations.append(&mut ban_move_if_spawn_can_land_in_corner_variations());
+        variations.append(&mut ban_move_if_game_over_chance_above_variations());
+        variations.append(&mut ban_move_if_empty_tiles_at_least_variations());
+        variations.append(&mut ban_move_if_unique_tiles_at_most_variations());
+        variations.append(&mut ban_move_if_breaks_sum_order_variations());
         //variations.append(&mut ban_move_if_column_not_locked_variations_subset());
         //variations.append(&mut ban_move_if_breaks_monotonicity_of_column_variations_subset());
         variations
     }
 
+    // Rules written in the condition language, these are not part of generate_all_variations as
+    // there are too many for the exhaustive searches
+    pub fn generate_condition_variations(max_terms: usize) -> Vec<Self> {
+        assert!(
+            max_terms <= MAX_TERMS,
+            "a condition has at most {} terms",
+            MAX_TERMS
+        );
+        let conditions = Condition::generate_all_variations(max_terms);
+        let mut variations = Vec::new();
+        for direction in Move::iterator() {
+            for &condition in conditions.iter() {
+                variations.push(BanMove::When(direction, condition));
+            }
+        }
+        variations
+    }
+
+    // Whether the parameters are in range, a percentage above 100 could be written down but would
+    // not be read back
+    pub fn is_valid(&self) -> bool {
+        match *self {
+            BanMove::IfGameOverChanceAbove(_, percentage) => percentage <= 100,
+            _ => true,
+        }
+    }
+
     pub fn get_move(&self) -> Move {
         match self {
             BanMove::Always(direction) => *direction,
@@ -98,6 +177,14 @@ impl BanMove {
             BanMove::MovesLargestTileOutOfCorner(direction, _) => *direction,
             BanMove::FillsColumn(direction, _) => *direction,
             BanMove::FillsRow(direction, _) => *direction,
+            BanMove::BreaksSnakeChain(direction, _) => *direction,
+            BanMove::EveryNextMoveLosesCorner(direction, _, _) => *direction,
+            BanMove::IfSpawnCanLandInCorner(direction, _) => *direction,
+            BanMove::IfGameOverChanceAbove(direction, _) => *direction,
+            BanMove::IfEmptyTilesAtLeast(direction, _) => *direction,
+            BanMove::IfUniqueTilesAtMost(direction, _) => *direction,
+            BanMove::BreaksSumOrder(direction, _) => *direction,
+            BanMove::When(direction, _) => *direction,
         }
     }
 }
@@ -145,6 +232,44 @@ impl fmt::Display for BanMove {
             BanMove::FillsRow(direction, row) => {
                 write!(f, "ban move {} if fills {} row", direction, row)
             }
+            BanMove::BreaksSnakeChain(direction, corner) => write!(
+                f,
+                "ban move {} if breaks snake chain from {} corner",
+                direction, corner
+            ),
+            BanMove::EveryNextMoveLosesCorner(direction, corner, spawn_case) => write!(
+                f,
+                "ban move {} if every next move loses {} corner in {}",
+                direction, corner, spawn_case
+            ),
+            BanMove::IfSpawnCanLandInCorner(direction, corner) => write!(
+                f,
+                "ban move {} if spawn can land in {} corner",
+                direction, corner
+            ),
+            BanMove::IfGameOverChanceAbove(direction, percentage) => write!(
+                f,
+                "ban move {} if chance of no moves above {}%",
+                direction, percentage
+            ),
+            BanMove::IfEmptyTilesAtLeast(direction, threshold) => write!(
+                f,
+                "ban move {} if at least {} empty tiles",
+                direction, threshold
+            ),
+            BanMove::IfUniqueTilesAtMost(direction, threshold) => write!(
+                f,
+                "ban move {} if at most {} unique tiles",
+                direction, threshold
+            ),
+            BanMove::BreaksSumOrder(direction, towards) => write!(
+                f,
+                "ban move {} if breaks sum order towards {}",
+                direction, towards
+            ),
+            BanMove::When(direction, condition) => {
+                write!(f, "ban move {} when {}", direction, condition)
+            }
         }
     }
 }
@@ -302,6 +427,105 @@ fn ban_move_if_fills_row<T: GameEngine>(
     None
 }
 
+// Compares the sum of the chain so merges inside the chain do not count as breaking it
+fn ban_move_if_breaks_snake_chain<T: GameEngine>(
+    engine: &T,
+    board: Board,
+    direction: Move,
+    corner: Corner,
+) -> Option<Move> {
+    let chain_sum = attributes::snake_chain_sum(board, corner);
+    let new_board = engine.shift(board, direction);
+    let new_chain_sum = attributes::snake_chain_sum(new_board, corner);
+    if new_chain_sum < chain_sum {
+        return Some(direction);
+    }
+    None
+}
+
+// In the worst case one spawn that leaves no move keeping the corner is enough to ban the move
+fn ban_move_if_every_next_move_loses_corner<T: GameEngine>(
+    engine: &T,
+    board: Board,
+    direction: Move,
+    corner: Corner,
+    spawn_case: SpawnCase,
+) -> Option<Move> {
+    let keeps_corner = |spawned| !attributes::does_every_move_lose_corner(engine, spawned, corner);
+    let corner_kept =
+        attributes::holds_after_spawn(engine, board, direction, spawn_case, keeps_corner);
+    if attributes::is_move_possible(engine, board, direction) && !corner_kept {
+        return Some(direction);
+    }
+    None
+}
+
+fn ban_move_if_spawn_can_land_in_corner<T: GameEngine>(
+    engine: &T,
+    board: Board,
+    direction: Move,
+    corner: Corner,
+) -> Option<Move> {
+    if attributes::get_corner_spawn_probability(engine, board, direction, corner) > 0. {
+        return Some(direction);
+    }
+    None
+}
+
+fn ban_move_if_game_over_chance_above<T: GameEngine>(
+    engine: &T,
+    board: Board,
+    direction: Move,
+    percentage: u64,
+) -> Option<Move> {
+    let probability = attributes::get_game_over_probability(engine, board, direction);
+    if probability * 100. > percentage as f64 {
+        return Some(direction);
+    }
+    None
+}
+
+fn ban_move_if_empty_tiles_at_least(board: Board, direction: Move, threshold: u64) -> Option<Move> {
+    if attributes::count_empty_tiles(board) >= threshold {
+        return Some(direction);
+    }
+    None
+}
+
+fn ban_move_if_unique_tiles_at_most(board: Board, direction: Move, threshold: u64) -> Option<Move> {
+    if attributes::count_unique_tiles(board) <= threshold {
+        return Some(direction);
+    }
+    None
+}
+
+fn ban_move_if_breaks_sum_order<T: GameEngine>(
+    engine: &T,
+    board: Board,
+    direction: Move,
+    towards: Move,
+) -> Option<Move> {
+    let is_ordered = attributes::is_sum_ordered(board, towards);
+    let new_board = engine.shift(board, direction);
+    let is_new_ordered = attributes::is_sum_ordered(new_board, towards);
+    if is_ordered && !is_new_ordered {
+        return Some(direction);
+    }
+    None
+}
+
+fn ban_move_when<T: GameEngine>(
+    engine: &T,
+    board: Board,
+    direction: Move,
+    condition: Condition,
+) -> Option<Move> {
+    if condition.holds(engine, board, direction) {
+        return Some(direction);
+    }
+    None
+}
+
 fn ban_move_if_column_not_locked_variations() -> Vec<BanMove> {
     let mut variations = Vec::new();
     for column in Column::iterator() {
@@ -470,3 +694,77 @@ fn ban_move_if_fills_row_variations() -> Vec<BanMove> {
     }
     variations
 }
+
+fn ban_move_if_breaks_snake_chain_variations() -> Vec<BanMove> {
+    let mut variations = Vec::new();
+    for direction in Move::iterator() {
+        for corner in Corner::iterator() {
+            variations.push(BanMove::BreaksSnakeChain(direction, corner));
+        }
+    }
+    variations
+}
+
+fn ban_move_if_every_next_move_loses_corner_variations() -> Vec<BanMove> {
+    let mut variations = Vec::new();
+    for direction in Move::iterator() {
+        for corner in Corner::iterator() {
+            for spawn_case in SpawnCase::iterator() {
+                variations.push(BanMove::EveryNextMoveLosesCorner(
+                    direction, corner, spawn_case,
+                ));
+            }
+        }
+    }
+    variations
+}
+
+fn ban_move_if_spawn_can_land_in_corner_variations() -> Vec<BanMove> {
+    let mut variations = Vec::new();
+    for direction in Move::iterator() {
+        for corner in Corner::iterator() {
+            variations.push(BanMove::IfSpawnCanLandInCorner(direction, corner));
+        }
+    }
+    variations
+}
+
+fn ban_move_if_game_over_chance_above_variations() -> Vec<BanMove> {
+    let mut variations = Vec::new();
+    for direction in Move::iterator() {
+        for &percentage in [0, 10, 25].iter() {
+            variations.push(BanMove::IfGameOverChanceAbove(direction, percentage));
+        }
+    }
+    variations
+}
+
+fn ban_move_if_empty_tiles_at_least_variations() -> Vec<BanMove> {
+    let mut variations = Vec::new();
+    for direction in Move::iterator() {
+        for &threshold in [4, 8, 12].iter() {
+            variations.push(BanMove::IfEmptyTilesAtLeast(direction, threshold));
+        }
+    }
+    variations
+}
+
+fn ban_move_if_unique_tiles_at_most_variations() -> Vec<BanMove> {
+    let mut variations = Vec::new();
+    for direction in Move::iterator() {
+        for &threshold in [3, 5, 7].iter() {
+            variations.push(BanMove::IfUniqueTilesAtMost(direction, threshold));
+        }
+    }
+    variations
+}
+
+fn ban_move_if_breaks_sum_order_variations() -> Vec<BanMove> {
+    let mut variations = Vec::new();
+    for direction in Move::iterator() {
+        for towards in Move::iterator() {
+            variations.push(BanMove::BreaksSumOrder(direction, towards));
+        }
+    }
+    variations
+}
@@ -0,0 +1,421 @@
+use super::attributes;
+use super::attributes::{Column, Corner, Row};
+use crate::engine::{Board, GameEngine, Move};
+use std::fmt;
+
+// A small language for rule conditions built from the predicates in attributes.rs. A term checks a
+// predicate on the board before or after the move, optionally negated. A condition joins up to
+// MAX_TERMS clauses with "and" or "or", and a clause is a single term or up to MAX_TERMS terms in
+// brackets joined by the other connective, e.g.
+//
+//   bottom row locked after and (vertical merge possible after or left column empty after)
+//
+// Without brackets "and" binds tighter than "or", so "a and b or c" is "(a and b) or c".
+//
+// Conditions are kept to a fixed size so rules stay Copy, which limits them to two levels: brackets
+// cannot be nested and "not" applies to a single term. Every boolean condition can still be
+// written in that form by moving the "not"s onto the terms, as long as it fits in the size.
+
+pub const MAX_TERMS: usize = 3;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Predicate {
+    ColumnLocked(Column),
+    RowLocked(Row),
+    ColumnMonotonic(Column),
+    RowMonotonic(Row),
+    ColumnEmpty(Column),
+    RowEmpty(Row),
+    LargestTileInCorner(Corner),
+    TwoLargestTilesAdjacent,
+    HorizontalMergePossible,
+    VerticalMergePossible,
+}
+
+impl Predicate {
+    pub fn holds(&self, board: Board) -> bool {
+        match self {
+            Predicate::ColumnLocked(column) => attributes::is_column_locked(board, *column),
+            Predicate::RowLocked(row) => attributes::is_row_locked(board, *row),
+            Predicate::ColumnMonotonic(column) => attributes::is_column_monotonic(board, *column),
+            Predicate::RowMonotonic(row) => attributes::is_row_monotonic(board, *row),
+            Predicate::ColumnEmpty(column) => attributes::is_column_empty(board, *column),
+            Predicate::RowEmpty(row) => attributes::is_row_empty(board, *row),
+            Predicate::LargestTileInCorner(corner) => {
+                attributes::is_largest_tile_in_corner(board, *corner)
+            }
+            Predicate::TwoLargestTilesAdjacent => attributes::are_2_largest_tiles_adjacent(board),
+            Predicate::HorizontalMergePossible => attributes::is_merge_possible(board, Move::Left),
+            Predicate::VerticalMergePossible => attributes::is_merge_possible(board, Move::Up),
+        }
+    }
+
+    pub fn generate_all_variations() -> Vec<Self> {
+        let mut variations = Vec::new();
+        for column in Column::iterator() {
+            variations.push(Predicate::ColumnLocked(column));
+            variations.push(Predicate::ColumnMonotonic(column));
+            variations.push(Predicate::ColumnEmpty(column));
+        }
+        for row in Row::iterator() {
+            variations.push(Predicate::RowLocked(row));
+            variations.push(Predicate::RowMonotonic(row));
+            variations.push(Predicate::RowEmpty(row));
+        }
+        for corner in Corner::iterator() {
+            variations.push(Predicate::LargestTileInCorner(corner));
+        }
+        variations.push(Predicate::TwoLargestTilesAdjacent);
+        variations.push(Predicate::HorizontalMergePossible);
+        variations.push(Predicate::VerticalMergePossible);
+        variations
+    }
+}
+
+impl fmt::Display for Predicate {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Predicate::ColumnLocked(column) => write!(f, "{} column locked", column),
+            Predicate::RowLocked(row) => write!(f, "{} row locked", row),
+            Predicate::ColumnMonotonic(column) => write!(f, "{} column monotonic", column),
+            Predicate::RowMonotonic(row) => write!(f, "{} row monotonic", row),
+            Predicate::ColumnEmpty(column) => write!(f, "{} column empty", column),
+            Predicate::RowEmpty(row) => write!(f, "{} row empty", row),
+            Predicate::LargestTileInCorner(corner) => {
+                write!(f, "largest tile in {} corner", corner)
+            }
+            Predicate::TwoLargestTilesAdjacent => write!(f, "2 largest tiles adjacent"),
+            Predicate::HorizontalMergePossible => write!(f, "horizontal merge possible"),
+            Predicate::VerticalMergePossible => write!(f, "vertical merge possible"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum When {
+    Before,
+    After,
+}
+
+impl fmt::Display for When {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            When::Before => write!(f, "before"),
+            When::After => write!(f, "after"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Term {
+    pub predicate: Predicate,
+    pub when: When,
+    pub negated: bool,
+}
+
+impl Term {
+    pub fn new(predicate: Predicate, when: When) -> Self {
+        Term {
+            predicate,
+            when,
+            negated: false,
+        }
+    }
+
+    pub fn not(predicate: Predicate, when: When) -> Self {
+        Term {
+            predicate,
+            when,
+            negated: true,
+        }
+    }
+
+    pub fn holds(&self, board: Board, new_board: Board) -> bool {
+        let checked_board = match self.when {
+            When::Before => board,
+            When::After => new_board,
+        };
+        self.predicate.holds(checked_board) != self.negated
+    }
+
+    pub fn generate_all_variations() -> Vec<Self> {
+        let mut variations = Vec::new();
+        for predicate in Predicate::generate_all_variations() {
+            for &when in [When::Before, When::After].iter() {
+                variations.push(Term::new(predicate, when));
+                variations.push(Term::not(predicate, when));
+            }
+        }
+        variations
+    }
+}
+
+impl fmt::Display for Term {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.negated {
+            write!(f, "not ")?;
+        }
+        write!(f, "{} {}", self.predicate, self.when)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Connective {
+    And,
+    Or,
+}
+
+impl fmt::Display for Connective {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Connective::And => write!(f, "and"),
+            Connective::Or => write!(f, "or"),
+        }
+    }
+}
+
+impl Connective {
+    // The connective joining the terms inside a clause
+    pub fn other(&self) -> Connective {
+        match self {
+            Connective::And => Connective::Or,
+            Connective::Or => Connective::And,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Clause {
+    // filled from the start, the first None ends the terms
+    terms: [Option<Term>; MAX_TERMS],
+}
+
+impl Clause {
+    // None if there are no terms or more than MAX_TERMS
+    fn new(terms: &[Term]) -> Option<Self> {
+        if terms.is_empty() || terms.len() > MAX_TERMS {
+            return None;
+        }
+        let mut clause_terms = [None; MAX_TERMS];
+        for (idx, &term) in terms.iter().enumerate() {
+            clause_terms[idx] = Some(term);
+        }
+        Some(Clause {
+            terms: clause_terms,
+        })
+    }
+
+    pub fn get_terms(&self) -> Vec<Term> {
+        self.terms.iter().map_while(|&term| term).collect()
+    }
+
+    fn holds(&self, connective: Connective, board: Board, new_board: Board) -> bool {
+        let mut terms = self.terms.iter().map_while(|&term| term);
+        match connective {
+            Connective::And => terms.all(|term| term.holds(board, new_board)),
+            Connective::Or => terms.any(|term| term.holds(board, new_board)),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Condition {
+    connective: Connective,
+    // filled from the start, the first None ends the clauses
+    clauses: [Option<Clause>; MAX_TERMS],
+}
+
+impl Condition {
+    // Joins single terms, None if there are no terms or more than MAX_TERMS
+    pub fn new(connective: Connective, terms: &[Term]) -> Option<Self> {
+        Condition::grouped(
+            connective,
+            &terms.iter().map(|&term| vec![term]).collect::<Vec<_>>(),
+        )
+    }
+
+    pub fn all(terms: &[Term]) -> Option<Self> {
+        Condition::new(Connective::And, terms)
+    }
+
+    pub fn any(terms: &[Term]) -> Option<Self> {
+        Condition::new(Connective::Or, terms)
+    }
+
+    // Joins clauses whose terms are joined by the other connective, e.g. grouped(And, [[a], [b, c]])
+    // is "a and (b or c)". None if there are no clauses, more than MAX_TERMS or a clause has the
+    // wrong number of terms.
+    pub fn grouped(connective: Connective, clauses: &[Vec<Term>]) -> Option<Self> {
+        if clauses.is_empty() || clauses.len() > MAX_TERMS {
+            return None;
+        }
+        // a single clause is the same as its terms joined without brackets
+        if clauses.len() == 1 && clauses[0].len() > 1 {
+            return Condition::new(connective.other(), &clauses[0]);
+        }
+        let mut condition_clauses = [None; MAX_TERMS];
+        for (idx, terms) in clauses.iter().enumerate() {
+            condition_clauses[idx] = Some(Clause::new(terms)?);
+        }
+        Some(Condition {
+            connective,
+            clauses: condition_clauses,
+        })
+    }
+
+    pub fn get_connective(&self) -> Connective {
+        self.connective
+    }
+
+    pub fn get_clauses(&self) -> Vec<Clause> {
+        self.clauses.iter().map_while(|&clause| clause).collect()
+    }
+
+    // Every term in the condition, whatever clause it is in
+    pub fn get_terms(&self) -> Vec<Term> {
+        self.get_clauses()
+            .iter()
+            .flat_map(|clause| clause.get_terms())
+            .collect()
+    }
+
+    pub fn holds<T: GameEngine>(&self, engine: &T, board: Board, direction: Move) -> bool {
+        let new_board = engine.shift(board, direction);
+        let inner = self.connective.other();
+        let mut clauses = self.clauses.iter().map_while(|&clause| clause);
+        match self.connective {
+            Connective::And => clauses.all(|clause| clause.holds(inner, board, new_board)),
+            Connective::Or => clauses.any(|clause| clause.holds(inner, board, new_board)),
+        }
+    }
+
+    // Every condition of up to max_terms distinct terms without brackets, the order of the terms
+    // does not matter so each set of terms is only generated once
+    pub fn generate_all_variations(max_terms: usize) -> Vec<Self> {
+        assert!(
+            max_terms <= MAX_TERMS,
+            "a condition has at most {} terms",
+            MAX_TERMS
+        );
+        let terms = Term::generate_all_variations();
+        let mut term_sets: Vec<Vec<usize>> = Vec::new();
+        let mut last_sets: Vec<Vec<usize>> = vec![vec![]];
+        for _ in 0..max_terms {
+            let mut next_sets = Vec::new();
+            for set in &last_sets {
+                let start = set.last().map_or(0, |&idx| idx + 1);
+                for idx in start..terms.len() {
+                    let mut next_set = set.clone();
+                    next_set.push(idx);
+                    next_sets.push(next_set);
+                }
+            }
+            term_sets.extend(next_sets.iter().cloned());
+            last_sets = next_sets;
+        }
+
+        let mut variations = Vec::new();
+        for set in term_sets {
+            let set_terms = set.iter().map(|&idx| terms[idx]).collect::<Vec<_>>();
+            variations.push(Condition::all(&set_terms).unwrap());
+            // a single term is the same whatever the connective
+            if set_terms.len() > 1 {
+                variations.push(Condition::any(&set_terms).unwrap());
+            }
+        }
+        variations
+    }
+}
+
+impl fmt::Display for Condition {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let inner_separator = format!(" {} ", self.connective.other());
+        let clauses = self
+            .get_clauses()
+            .iter()
+            .map(|clause| {
+                let terms = clause
+                    .get_terms()
+                    .iter()
+                    .map(|term| term.to_string())
+                    .collect::<Vec<_>>();
+                if terms.len() == 1 {
+                    terms[0].clone()
+                } else {
+                    format!("({})", terms.join(&inner_separator))
+                }
+            })
+            .collect::<Vec<_>>();
+        write!(f, "{}", clauses.join(&format!(" {} ", self.connective)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::GameEngineNoStores;
+
+    #[test]
+    fn it_condition_holds() {
+        let engine = GameEngineNoStores;
+        // keeps the bottom row locked and produces a merge
+        let condition = Condition::all(&[
+            Term::new(Predicate::RowLocked(Row::Bottom), When::After),
+            Term::not(Predicate::VerticalMergePossible, When::Before),
+            Term::new(Predicate::VerticalMergePossible, When::After),
+        ])
+        .unwrap();
+        assert_eq!(
+            condition.to_string(),
+            "bottom row locked after and not vertical merge possible before and vertical merge possible after"
+        );
+        let board = 0x0000000220001234;
+        assert!(condition.holds(&engine, board, Move::Left));
+        assert!(!condition.holds(&engine, board, Move::Up));
+
+        let either = Condition::any(&[
+            Term::new(Predicate::RowEmpty(Row::Top), When::Before),
+            Term::new(
+                Predicate::LargestTileInCorner(Corner::BottomLeft),
+                When::After,
+            ),
+        ])
+        .unwrap();
+        assert!(either.holds(&engine, 0x0000000000000012, Move::Right));
+        assert!(!either.holds(&engine, 0x1000000000000002, Move::Right));
+        assert!(Condition::all(&[]).is_none());
+
+        // left column empty before and (top row empty after or vertical merge possible after)
+        let grouped = Condition::grouped(
+            Connective::And,
+            &[
+                vec![Term::new(
+                    Predicate::ColumnEmpty(Column::Left),
+                    When::Before,
+                )],
+                vec![
+                    Term::new(Predicate::RowEmpty(Row::Top), When::After),
+                    Term::new(Predicate::VerticalMergePossible, When::After),
+                ],
+            ],
+        )
+        .unwrap();
+        assert_eq!(
+            grouped.to_string(),
+            "left column empty before and (top row empty after or vertical merge possible after)"
+        );
+        assert!(grouped.holds(&engine, 0x0100000000000000, Move::Down));
+        assert!(!grouped.holds(&engine, 0x0100000000000000, Move::Left));
+        assert!(!grouped.holds(&engine, 0x1000000000000000, Move::Down));
+    }
+
+    #[test]
+    fn it_generate_conditions() {
+        let num_terms = Term::generate_all_variations().len();
+        assert_eq!(Condition::generate_all_variations(1).len(), num_terms);
+        assert_eq!(
+            Condition::generate_all_variations(2).len(),
+            num_terms + num_terms * (num_terms - 1)
+        );
+    }
+}
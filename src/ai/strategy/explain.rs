@@ -0,0 +1,201 @@
+use super::attributes;
+use super::ban_rules::BanMove;
+use super::try_rules::TryMove;
+use super::Strategy;
+use crate::ai::Explain;
+use crate::engine::{Board, GameEngine, Move};
+use std::fmt;
+
+// Follows the same steps as Strategy::get_next_move, recording each decision on the way
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TryOutcome {
+    Chosen,
+    // The condition of the rule did not hold on the board
+    NotTriggered,
+    Banned,
+    // The rule fired but the move does not change the board
+    Impossible,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FallbackOutcome {
+    // The first fallback move that is possible and not banned
+    Allowed(Move),
+    // Every possible fallback move was banned so the banned policy chose a banned move to make
+    Forced(Move),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct StrategyExplanation {
+    // The ban rules that fired, with the move they banned
+    pub fired_bans: Vec<(BanMove, Move)>,
+    // The try rules checked in order, stops at the chosen rule
+    pub try_outcomes: Vec<(TryMove, TryOutcome)>,
+    // None if a try rule was chosen or no move is possible
+    pub fallback: Option<FallbackOutcome>,
+    pub chosen_move: Option<Move>,
+}
+
+impl StrategyExplanation {
+    // A short description of what decided the move
+    pub fn get_used_rule(&self) -> String {
+        if let Some(&(try_rule, _)) = self
+            .try_outcomes
+            .iter()
+            .find(|&&(_, outcome)| outcome == TryOutcome::Chosen)
+        {
+            return try_rule.to_string();
+        }
+        match self.fallback {
+            Some(FallbackOutcome::Allowed(direction)) => format!("initial fallback {}", direction),
+            Some(FallbackOutcome::Forced(direction)) => format!("{} forced in fallback", direction),
+            None => "no possible move".to_string(),
+        }
+    }
+}
+
+impl fmt::Display for StrategyExplanation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.chosen_move {
+            Some(direction) => {
+                writeln!(f, "Move {} chosen by: {}", direction, self.get_used_rule())?
+            }
+            None => writeln!(f, "No move is possible")?,
+        }
+        if self.fired_bans.is_empty() {
+            writeln!(f, "No moves banned")?;
+        }
+        for (ban_rule, direction) in &self.fired_bans {
+            writeln!(f, "Banned {}: {}", direction, ban_rule)?;
+        }
+        for (try_rule, outcome) in &self.try_outcomes {
+            let reason = match outcome {
+                TryOutcome::Chosen => "chosen",
+                TryOutcome::NotTriggered => "skipped, condition not met",
+                TryOutcome::Banned => "skipped, move is banned",
+                TryOutcome::Impossible => "skipped, move is not possible",
+            };
+            writeln!(f, "{}: {}", try_rule, reason)?;
+        }
+        Ok(())
+    }
+}
+
+impl Explain for Strategy {
+    type Explanation = StrategyExplanation;
+
+    fn explain<T: GameEngine>(&mut self, engine: &T, board: Board) -> StrategyExplanation {
+        let fired_bans = self
+            .ban_rules
+            .iter()
+            .filter_map(|&ban_rule| {
+                ban_rule
+                    .execute(engine, board)
+                    .map(|direction| (ban_rule, direction))
+            })
+            .collect::<Vec<_>>();
+        let banned_moves = fired_bans
+            .iter()
+            .map(|&(_, direction)| direction)
+            .collect::<Vec<_>>();
+
+        let mut try_outcomes = Vec::new();
+        for &try_rule in self.try_rules.iter() {
+            let outcome = match try_rule.execute(engine, board) {
+                None => TryOutcome::NotTriggered,
+                Some(direction) if banned_moves.contains(&direction) => TryOutcome::Banned,
+                Some(direction) if !attributes::is_move_possible(engine, board, direction) => {
+                    TryOutcome::Impossible
+                }
+                Some(direction) => {
+                    try_outcomes.push((try_rule, TryOutcome::Chosen));
+                    return StrategyExplanation {
+                        fired_bans,
+                        try_outcomes,
+                        fallback: None,
+                        chosen_move: Some(direction),
+                    };
+                }
+            };
+            try_outcomes.push((try_rule, outcome));
+        }
+
+        let fallback_order = self.get_fallback_order(engine, board);
+        let fallback = match fallback_order.iter().find(|&&direction| {
+            !banned_moves.contains(&direction)
+                && attributes::is_move_possible(engine, board, direction)
+        }) {
+            Some(&direction) => Some(FallbackOutcome::Allowed(direction)),
+            None => self
+                .rank_forced_moves(engine, board, &fallback_order)
+                .first()
+                .map(|&direction| FallbackOutcome::Forced(direction)),
+        };
+        let chosen_move = fallback.map(|outcome| match outcome {
+            FallbackOutcome::Allowed(direction) | FallbackOutcome::Forced(direction) => direction,
+        });
+        StrategyExplanation {
+            fired_bans,
+            try_outcomes,
+            fallback,
+            chosen_move,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::attributes::{Column, Corner};
+    use super::*;
+    use crate::ai::AI;
+    use crate::engine::GameEngineNoStores;
+
+    #[test]
+    fn it_explain_strategy() {
+        let engine = GameEngineNoStores;
+        let mut strategy = Strategy::new(
+            &vec![BanMove::IfColumnNotLocked(Move::Up, Column::Left)],
+            &vec![
+                TryMove::ProducesMerge(Move::Up),
+                TryMove::IfMovesLargestTileToCorner(Move::Left, Corner::BottomLeft),
+            ],
+            &vec![Move::Down, Move::Up, Move::Left, Move::Right],
+        )
+        .unwrap();
+
+        let board = 0x0000000000001234;
+        let explanation = strategy.explain(&engine, board);
+        assert_eq!(
+            explanation.fired_bans,
+            vec![(BanMove::IfColumnNotLocked(Move::Up, Column::Left), Move::Up)]
+        );
+        assert_eq!(
+            explanation.try_outcomes,
+            vec![
+                (TryMove::ProducesMerge(Move::Up), TryOutcome::NotTriggered),
+                (
+                    TryMove::IfMovesLargestTileToCorner(Move::Left, Corner::BottomLeft),
+                    TryOutcome::NotTriggered
+                ),
+            ]
+        );
+        assert_eq!(
+            explanation.fallback,
+            Some(FallbackOutcome::Forced(Move::Up))
+        );
+        assert_eq!(explanation.get_used_rule(), "up forced in fallback");
+        assert_eq!(
+            explanation.chosen_move,
+            strategy.get_next_move(&engine, board)
+        );
+
+        let board = 0x0000000000000021;
+        let explanation = strategy.explain(&engine, board);
+        assert_eq!(explanation.chosen_move, Some(Move::Left));
+        assert_eq!(
+            explanation.get_used_rule(),
+            "try move left if moves largest tile to bottom left corner"
+        );
+    }
+}
@@ -0,0 +1,202 @@
+use super::attributes;
+use super::attributes::Corner;
+use super::condition::Condition;
+use crate::engine::{Board, GameEngine, Move};
+use std::fmt;
+
+// The fallback moves are a fixed order, two things can change what happens once the try rules
+// have not chosen a move:
+//
+// - fallback preferences move a fallback move ahead of another while their condition holds, e.g.
+//   "prefer right over up when left column locked before". The condition is checked for the
+//   preferred move, the preferences apply in the order they are listed.
+// - the banned policy picks the move to force when every possible fallback move is banned, either
+//   the first one in the fallback order or the one that does the least harm by an attribute.
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FallbackPreference {
+    pub preferred: Move,
+    pub over: Move,
+    pub condition: Condition,
+}
+
+impl FallbackPreference {
+    pub fn new(preferred: Move, over: Move, condition: Condition) -> Self {
+        FallbackPreference {
+            preferred,
+            over,
+            condition,
+        }
+    }
+
+    pub fn holds<T: GameEngine>(&self, engine: &T, board: Board) -> bool {
+        self.condition.holds(engine, board, self.preferred)
+    }
+}
+
+impl fmt::Display for FallbackPreference {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "prefer {} over {} when {}",
+            self.preferred, self.over, self.condition
+        )
+    }
+}
+
+// The fallback moves reordered by the preferences that hold on the board
+pub fn order_fallback_moves<T: GameEngine>(
+    engine: &T,
+    board: Board,
+    fallback_moves: &[Move],
+    preferences: &[FallbackPreference],
+) -> Vec<Move> {
+    let mut order = fallback_moves.to_vec();
+    for preference in preferences {
+        let preferred_idx = order.iter().position(|&dir| dir == preference.preferred);
+        let over_idx = order.iter().position(|&dir| dir == preference.over);
+        if let (Some(preferred_idx), Some(over_idx)) = (preferred_idx, over_idx) {
+            if preferred_idx > over_idx && preference.holds(engine, board) {
+                let preferred = order.remove(preferred_idx);
+                order.insert(over_idx, preferred);
+            }
+        }
+    }
+    order
+}
+
+// How much of the position a move keeps, higher is less harmful
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum HarmScore {
+    EmptyTiles,
+    SnakeChain(Corner),
+    Gradient(Corner),
+}
+
+impl HarmScore {
+    pub fn score<T: GameEngine>(&self, engine: &T, board: Board, direction: Move) -> f64 {
+        let new_board = engine.shift(board, direction);
+        match *self {
+            HarmScore::EmptyTiles => attributes::count_empty_tiles(new_board) as f64,
+            HarmScore::SnakeChain(corner) => attributes::snake_chain_sum(new_board, corner) as f64,
+            HarmScore::Gradient(corner) => attributes::gradient_conformity(new_board, corner),
+        }
+    }
+}
+
+impl fmt::Display for HarmScore {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            HarmScore::EmptyTiles => write!(f, "empty tiles"),
+            HarmScore::SnakeChain(corner) => write!(f, "snake chain from {} corner", corner),
+            HarmScore::Gradient(corner) => write!(f, "gradient towards {} corner", corner),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum BannedPolicy {
+    // The first possible move in the fallback order, what strategies have always done
+    #[default]
+    FirstLegal,
+    LeastHarmful(HarmScore),
+}
+
+impl BannedPolicy {
+    // The banned moves from the one to force first to the one to force last, the moves are given
+    // in the fallback order which breaks ties
+    pub fn rank_banned_moves<T: GameEngine>(
+        &self,
+        engine: &T,
+        board: Board,
+        banned_moves: &[Move],
+    ) -> Vec<Move> {
+        let mut ranked = banned_moves.to_vec();
+        if let BannedPolicy::LeastHarmful(harm_score) = self {
+            let mut scored = ranked
+                .iter()
+                .map(|&direction| (direction, harm_score.score(engine, board, direction)))
+                .collect::<Vec<_>>();
+            scored.sort_by(|(_, a), (_, b)| b.partial_cmp(a).unwrap());
+            ranked = scored.into_iter().map(|(direction, _)| direction).collect();
+        }
+        ranked
+    }
+
+    pub fn generate_all_variations() -> Vec<Self> {
+        let mut variations = vec![
+            BannedPolicy::FirstLegal,
+            BannedPolicy::LeastHarmful(HarmScore::EmptyTiles),
+        ];
+        for corner in Corner::iterator() {
+            variations.push(BannedPolicy::LeastHarmful(HarmScore::SnakeChain(corner)));
+            variations.push(BannedPolicy::LeastHarmful(HarmScore::Gradient(corner)));
+        }
+        variations
+    }
+}
+
+impl fmt::Display for BannedPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BannedPolicy::FirstLegal => write!(f, "first legal"),
+            BannedPolicy::LeastHarmful(harm_score) => write!(f, "least harmful by {}", harm_score),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::attributes::Column;
+    use super::super::condition::{Predicate, Term, When};
+    use super::*;
+    use crate::engine::GameEngineNoStores;
+
+    #[test]
+    fn it_order_fallback_moves() {
+        let engine = GameEngineNoStores;
+        let preference = FallbackPreference::new(
+            Move::Right,
+            Move::Up,
+            Condition::all(&[Term::new(
+                Predicate::ColumnLocked(Column::Left),
+                When::Before,
+            )])
+            .unwrap(),
+        );
+        assert_eq!(
+            preference.to_string(),
+            "prefer right over up when left column locked before"
+        );
+        let fallback_moves = vec![Move::Left, Move::Up, Move::Down, Move::Right];
+        // the left column is full with no merges so it is locked
+        assert_eq!(
+            order_fallback_moves(&engine, 0x1000200030004000, &fallback_moves, &[preference]),
+            vec![Move::Left, Move::Right, Move::Up, Move::Down]
+        );
+        assert_eq!(
+            order_fallback_moves(&engine, 0x1000100030004000, &fallback_moves, &[preference]),
+            fallback_moves
+        );
+    }
+
+    #[test]
+    fn it_rank_banned_moves() {
+        let engine = GameEngineNoStores;
+        let board = 0x1100000000000000;
+        let banned_moves = vec![Move::Down, Move::Left];
+        assert_eq!(
+            BannedPolicy::FirstLegal.rank_banned_moves(&engine, board, &banned_moves),
+            banned_moves
+        );
+        // left merges the two tiles and leaves more empty tiles
+        assert_eq!(
+            BannedPolicy::LeastHarmful(HarmScore::EmptyTiles).rank_banned_moves(
+                &engine,
+                board,
+                &banned_moves
+            ),
+            vec![Move::Left, Move::Down]
+        );
+    }
+}
@@ -16,9 +16,10 @@
  */
 use self::ban_rules::BanMove;
 use self::ban_rules::BanRules;
+use self::fallback::{BannedPolicy, FallbackPreference};
 use self::try_rules::TryMove;
 use self::try_rules::TryRules;
-use crate::ai::AI;
+use crate::ai::{MoveScores, ScoreMoves, AI};
 use crate::engine::Board;
 use crate::engine::GameEngine;
 use crate::engine::Move;
@@ -26,17 +27,32 @@ use std::fmt;
 
 pub mod attributes;
 pub mod ban_rules;
+pub mod condition;
 pub mod evaluate_strategies;
+pub mod explain;
+pub mod fallback;
 pub mod generate_strategies;
 pub mod mann_whitney;
+pub mod parse;
+pub mod phased;
 pub mod search;
 pub mod try_rules;
+pub mod weighted;
+
+// The snake strategy used on the website and in the user study, in the strategy file format
+pub const SNAKE: &str = include_str!("../../../strategies/snake.txt");
+
+pub fn snake() -> Strategy {
+    parse::parse_strategy_file(SNAKE).expect("The bundled snake strategy is invalid")
+}
 
 #[derive(Clone, PartialEq, Debug)]
 pub struct Strategy {
     ban_rules: BanRules,
     try_rules: TryRules,
     fallback_moves: Vec<Move>,
+    fallback_preferences: Vec<FallbackPreference>,
+    banned_policy: BannedPolicy,
 }
 
 #[derive(Clone, Copy, PartialEq, Debug)]
@@ -62,6 +78,10 @@ impl Strategy {
         try_rules: &TryRules,
         fallback_moves: &Vec<Move>,
     ) -> Option<Self> {
+        if !ban_rules.iter().all(|ban_rule| ban_rule.is_valid()) {
+            return None;
+        }
+
         // if last try move direction is same as first fallback move it is a redundant strategy
         if let Some(try_rule) = try_rules.last() {
             let last_try_direction = try_rule.get_move();
@@ -90,9 +110,64 @@ impl Strategy {
             ban_rules: ban_rules.clone(),
             try_rules: try_rules.clone(),
             fallback_moves: fallback_moves.clone(),
+            fallback_preferences: Vec::new(),
+            banned_policy: BannedPolicy::default(),
         })
     }
 
+    pub fn with_fallback_preferences(
+        mut self,
+        fallback_preferences: Vec<FallbackPreference>,
+    ) -> Self {
+        self.fallback_preferences = fallback_preferences;
+        self
+    }
+
+    pub fn with_banned_policy(mut self, banned_policy: BannedPolicy) -> Self {
+        self.banned_policy = banned_policy;
+        self
+    }
+
+    pub fn get_fallback_preferences(&self) -> &Vec<FallbackPreference> {
+        &self.fallback_preferences
+    }
+
+    pub fn get_banned_policy(&self) -> BannedPolicy {
+        self.banned_policy
+    }
+
+    // The fallback moves in the order the preferences give them on this board
+    pub fn get_fallback_order<T: GameEngine>(&self, engine: &T, board: Board) -> Vec<Move> {
+        fallback::order_fallback_moves(
+            engine,
+            board,
+            &self.fallback_moves,
+            &self.fallback_preferences,
+        )
+    }
+
+    // The possible fallback moves when every one of them is banned, ordered by the banned policy
+    fn rank_forced_moves<T: GameEngine>(
+        &self,
+        engine: &T,
+        board: Board,
+        fallback_order: &[Move],
+    ) -> Vec<Move> {
+        let possible_moves = fallback_order
+            .iter()
+            .cloned()
+            .filter(|&direction| attributes::is_move_possible(engine, board, direction))
+            .collect::<Vec<_>>();
+        self.banned_policy
+            .rank_banned_moves(engine, board, &possible_moves)
+    }
+
+    // Keeps the fallback preferences and banned policy when the rules are changed
+    fn with_fallback_config_of(self, other: &Strategy) -> Self {
+        self.with_fallback_preferences(other.fallback_preferences.clone())
+            .with_banned_policy(other.banned_policy)
+    }
+
     pub fn get_rules_ban_first(&self) -> Rules {
         let mut ban_rules = self
             .ban_rules
@@ -123,6 +198,19 @@ impl Strategy {
         try_rules
     }
 
+    // The moves banned by the ban rules on this board, the strategy only makes them when no other
+    // move is possible
+    pub fn get_banned_moves<T: GameEngine>(&self, engine: &T, board: Board) -> Vec<Move> {
+        let mut banned_moves = Vec::new();
+        for ban_rule in self.ban_rules.iter() {
+            match ban_rule.execute(engine, board) {
+                Some(direction) => banned_moves.push(direction),
+                None => (),
+            }
+        }
+        banned_moves
+    }
+
     pub fn swap_ban_rule(&self, rule_to_swap: BanMove, new_rule: BanMove) -> Option<Self> {
         Strategy::new(
             &self
@@ -133,6 +221,7 @@ impl Strategy {
             &self.try_rules,
             &self.fallback_moves,
         )
+        .map(|strategy| strategy.with_fallback_config_of(self))
     }
 
     pub fn swap_try_rule(&self, rule_to_swap: TryMove, new_rule: TryMove) -> Option<Self> {
@@ -145,18 +234,13 @@ impl Strategy {
                 .collect::<TryRules>(),
             &self.fallback_moves,
         )
+        .map(|strategy| strategy.with_fallback_config_of(self))
     }
 }
 
 impl AI for Strategy {
     fn get_next_move<T: GameEngine>(&mut self, engine: &T, board: Board) -> Option<Move> {
-        let mut banned_moves = Vec::new();
-        for ban_rule in self.ban_rules.iter() {
-            match ban_rule.execute(engine, board) {
-                Some(direction) => banned_moves.push(direction),
-                None => (),
-            }
-        }
+        let banned_moves = self.get_banned_moves(engine, board);
 
         for try_rule in self.try_rules.iter() {
             match try_rule.execute(engine, board) {
@@ -172,19 +256,57 @@ impl AI for Strategy {
         }
 
         // try to make the fallback moves before forcing them
-        for &direction in self.fallback_moves.iter() {
+        let fallback_order = self.get_fallback_order(engine, board);
+        for &direction in fallback_order.iter() {
             if !banned_moves.contains(&direction) {
                 if attributes::is_move_possible(engine, board, direction) {
                     return Some(direction);
                 }
             }
         }
-        for &direction in self.fallback_moves.iter() {
-            if attributes::is_move_possible(engine, board, direction) {
-                return Some(direction);
+        self.rank_forced_moves(engine, board, &fallback_order)
+            .first()
+            .cloned()
+    }
+}
+
+impl ScoreMoves for Strategy {
+    // The preference order of the strategy: the moves of the try rules that fire, then the allowed
+    // fallback moves and then the banned fallback moves in the order the banned policy forces them.
+    // The preferred move scores the number of moves ranked, the least preferred scores 1.
+    fn score_moves<T: GameEngine>(&mut self, engine: &T, board: Board) -> MoveScores {
+        let banned_moves = self.get_banned_moves(engine, board);
+        let mut preference_order: Vec<Move> = Vec::new();
+        let mut add_move = |direction: Move| {
+            if !preference_order.contains(&direction)
+                && attributes::is_move_possible(engine, board, direction)
+            {
+                preference_order.push(direction);
+            }
+        };
+        for try_rule in self.try_rules.iter() {
+            if let Some(direction) = try_rule.execute(engine, board) {
+                if !banned_moves.contains(&direction) {
+                    add_move(direction);
+                }
+            }
+        }
+        let fallback_order = self.get_fallback_order(engine, board);
+        for &direction in fallback_order.iter() {
+            if !banned_moves.contains(&direction) {
+                add_move(direction);
             }
         }
-        None
+        for direction in self.rank_forced_moves(engine, board, &fallback_order) {
+            add_move(direction);
+        }
+
+        let mut move_scores = MoveScores::new();
+        let num_moves = preference_order.len();
+        for (idx, &direction) in preference_order.iter().enumerate() {
+            move_scores.insert(direction, (num_moves - idx) as f64);
+        }
+        move_scores
     }
 }
 
@@ -196,7 +318,19 @@ impl fmt::Display for Strategy {
             vec_to_string_for_csv(&self.ban_rules),
             vec_to_string_for_csv(&self.try_rules),
             vec_to_string_for_csv(&self.fallback_moves),
-        )
+        )?;
+        // only written when set so strategies without them keep their old form
+        if !self.fallback_preferences.is_empty() {
+            write!(
+                f,
+                "\tFallback Preferences: {}",
+                vec_to_string_for_csv(&self.fallback_preferences)
+            )?;
+        }
+        if self.banned_policy != BannedPolicy::default() {
+            write!(f, "\tAll Banned: {}", self.banned_policy)?;
+        }
+        Ok(())
     }
 }
 
@@ -216,6 +350,7 @@ fn vec_to_string_for_csv<T: fmt::Display>(vec: &[T]) -> String {
 mod tests {
     use super::attributes::Column;
     use super::*;
+    use crate::engine::GameEngineNoStores;
 
     #[test]
     fn it_new_strategy() {
@@ -230,6 +365,79 @@ mod tests {
         );
     }
 
+    #[test]
+    fn it_score_moves() {
+        let engine = GameEngineNoStores;
+        let mut strategy = Strategy::new(
+            &vec![BanMove::IfColumnNotLocked(Move::Up, Column::Left)],
+            &vec![TryMove::ProducesMerge(Move::Down)],
+            &vec![Move::Left, Move::Up, Move::Down, Move::Right],
+        )
+        .unwrap();
+        // up is banned so it is least preferred
+        let board = 0x0000010000000000;
+        let move_scores = strategy.score_moves(&engine, board);
+        assert_eq!(move_scores.best(), strategy.get_next_move(&engine, board));
+        assert_eq!(
+            move_scores.ranked(),
+            vec![
+                (Move::Left, 4.),
+                (Move::Down, 3.),
+                (Move::Right, 2.),
+                (Move::Up, 1.)
+            ]
+        );
+    }
+
+    #[test]
+    fn it_fallback_config() {
+        let engine = GameEngineNoStores;
+        let board = 0x1100000000000000;
+        let strategy = Strategy::new(
+            &Move::iterator().map(BanMove::Always).collect(),
+            &vec![],
+            &vec![Move::Down, Move::Left, Move::Up, Move::Right],
+        )
+        .unwrap();
+        assert_eq!(
+            strategy.clone().get_next_move(&engine, board),
+            Some(Move::Down)
+        );
+        // left and right both merge, left is earlier in the fallback order
+        let mut least_harmful = strategy
+            .clone()
+            .with_banned_policy(BannedPolicy::LeastHarmful(fallback::HarmScore::EmptyTiles));
+        assert_eq!(
+            least_harmful.get_next_move(&engine, board),
+            Some(Move::Left)
+        );
+        assert_eq!(
+            least_harmful.score_moves(&engine, board).best(),
+            Some(Move::Left)
+        );
+
+        let mut preferred = Strategy::new(
+            &vec![],
+            &vec![],
+            &vec![Move::Down, Move::Left, Move::Up, Move::Right],
+        )
+        .unwrap()
+        .with_fallback_preferences(vec![FallbackPreference::new(
+            Move::Right,
+            Move::Down,
+            condition::Condition::all(&[condition::Term::new(
+                condition::Predicate::HorizontalMergePossible,
+                condition::When::Before,
+            )])
+            .unwrap(),
+        )]);
+        assert_eq!(preferred.get_next_move(&engine, board), Some(Move::Right));
+        assert_eq!(
+            preferred.get_next_move(&engine, 0x1200000000000000),
+            Some(Move::Down)
+        );
+    }
+
     #[test]
     fn it_get_rules() {
         let strategy = Strategy::new(
@@ -0,0 +1,978 @@
+use super::attributes::{Column, Corner, Row, SpawnCase};
+use super::ban_rules::{BanMove, BanRules};
+use super::condition::{Condition, Connective, Term};
+use super::fallback::{BannedPolicy, FallbackPreference};
+use super::try_rules::{TryMove, TryRules};
+use super::Strategy;
+use crate::engine::Move;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::str::FromStr;
+
+// Parses the text written by the Display implementations, so strategies saved in the search
+// results can be read back. Small sets of values such as moves and corners are parsed by finding
+// the value whose Display text matches. Rules are parsed by their wording with the parameters
+// parsed on their own, so any value of a parameter reads back, not just the ones the search uses,
+// and rules written in the condition language are parsed term by term.
+//
+// Strategy files have one rule per line, written as Display writes it, e.g.
+//
+//   # keep the largest tile in the corner
+//   ban move up if left column not locked
+//   try move left if moves largest tile to bottom left corner
+//   fallback left->up->down->right
+//   prefer right over up when left column locked before
+//   all banned least harmful by empty tiles
+//
+// Ban and try rules apply in the order they are listed, the fallback order is the line starting
+// with "fallback". Fallback preferences and the "all banned" policy are optional, without them the
+// fallback order is fixed and the first possible move is forced. Blank lines and lines starting
+// with # are ignored.
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ParseError {
+    // (what was being parsed, the text)
+    UnknownValue(&'static str, String),
+    // (section, 1-based position in the section, the error for the item)
+    InvalidItem(&'static str, usize, Box<ParseError>),
+    // (what was being parsed, the text) for a value outside its range
+    OutOfRange(&'static str, String),
+    // The section of the strategy that could not be found
+    MissingSection(&'static str),
+    // Strategy::new rejected the rules
+    InvalidStrategy(String),
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseError::UnknownValue(kind, text) => write!(f, "unknown {}: \"{}\"", kind, text),
+            ParseError::InvalidItem(section, position, error) => {
+                write!(f, "{} {}: {}", section, position, error)
+            }
+            ParseError::OutOfRange(kind, text) => write!(f, "{} out of range: \"{}\"", kind, text),
+            ParseError::MissingSection(section) => write!(f, "missing section \"{}\"", section),
+            ParseError::InvalidStrategy(text) => {
+                write!(f, "the rules do not make a valid strategy: \"{}\"", text)
+            }
+        }
+    }
+}
+
+impl Error for ParseError {}
+
+fn parse_by_display<T: fmt::Display>(
+    kind: &'static str,
+    candidates: impl Iterator<Item = T>,
+    text: &str,
+) -> Result<T, ParseError> {
+    let text = text.trim();
+    candidates
+        .into_iter()
+        .find(|candidate| candidate.to_string() == text)
+        .ok_or_else(|| ParseError::UnknownValue(kind, text.to_string()))
+}
+
+impl FromStr for Move {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        parse_by_display("move", Move::iterator(), s)
+    }
+}
+
+impl FromStr for Column {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        parse_by_display("column", Column::iterator(), s)
+    }
+}
+
+impl FromStr for Row {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        parse_by_display("row", Row::iterator(), s)
+    }
+}
+
+impl FromStr for Corner {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        parse_by_display("corner", Corner::iterator(), s)
+    }
+}
+
+impl FromStr for Term {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        parse_by_display("term", Term::generate_all_variations().into_iter(), s)
+    }
+}
+
+// There are too many conditions to match against Display, so the terms are parsed one at a time.
+// "and" binds tighter than "or" and brackets group terms joined by the other connective, only one
+// level of brackets is allowed.
+impl FromStr for Condition {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let unknown_condition = || ParseError::UnknownValue("condition", s.to_string());
+        let or_parts = split_outside_brackets(s, " or ").ok_or_else(unknown_condition)?;
+        let (connective, parts) = if or_parts.len() > 1 {
+            (Connective::Or, or_parts)
+        } else {
+            (
+                Connective::And,
+                split_outside_brackets(s, " and ").ok_or_else(unknown_condition)?,
+            )
+        };
+        let clauses = parts
+            .iter()
+            .enumerate()
+            .map(|(idx, part)| {
+                parse_clause(part, connective.other())
+                    .map_err(|error| ParseError::InvalidItem("term", idx + 1, Box::new(error)))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Condition::grouped(connective, &clauses).ok_or_else(unknown_condition)
+    }
+}
+
+// The terms of a clause joined by the inner connective, either in brackets or, for "and" inside
+// "or", without them
+fn parse_clause(clause: &str, inner: Connective) -> Result<Vec<Term>, ParseError> {
+    let clause = clause.trim();
+    let terms = match clause
+        .strip_prefix('(')
+        .and_then(|clause| clause.strip_suffix(')'))
+    {
+        Some(grouped) => {
+            // brackets cannot be nested and only hold the inner connective
+            if grouped.contains(['(', ')']) || grouped.contains(&format!(" {} ", inner.other())) {
+                return Err(ParseError::UnknownValue("clause", clause.to_string()));
+            }
+            grouped
+        }
+        None => clause,
+    };
+    if terms.len() == clause.len() && clause.contains(['(', ')']) {
+        return Err(ParseError::UnknownValue("clause", clause.to_string()));
+    }
+    terms
+        .split(&format!(" {} ", inner))
+        .map(|term| term.parse::<Term>())
+        .collect()
+}
+
+// None if the brackets do not match
+fn split_outside_brackets<'a>(s: &'a str, separator: &str) -> Option<Vec<&'a str>> {
+    let mut parts = Vec::new();
+    let mut depth = 0;
+    let mut start = 0;
+    for (idx, c) in s.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' if depth == 0 => return None,
+            ')' => depth -= 1,
+            _ if depth == 0 && s[idx..].starts_with(separator) => {
+                parts.push(&s[start..idx]);
+                start = idx + separator.len();
+            }
+            _ => (),
+        }
+    }
+    if depth != 0 {
+        return None;
+    }
+    parts.push(&s[start..]);
+    Some(parts)
+}
+
+// "prefer <move> over <move> when <condition>"
+impl FromStr for FallbackPreference {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let unknown_preference = || ParseError::UnknownValue("fallback preference", s.to_string());
+        let (moves, condition) = s
+            .trim()
+            .strip_prefix("prefer ")
+            .and_then(|preference| preference.split_once(" when "))
+            .ok_or_else(unknown_preference)?;
+        let (preferred, over) = moves.split_once(" over ").ok_or_else(unknown_preference)?;
+        Ok(FallbackPreference::new(
+            preferred.parse::<Move>()?,
+            over.parse::<Move>()?,
+            condition.parse::<Condition>()?,
+        ))
+    }
+}
+
+impl FromStr for BannedPolicy {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        parse_by_display(
+            "banned policy",
+            BannedPolicy::generate_all_variations().into_iter(),
+            s,
+        )
+    }
+}
+
+impl FromStr for SpawnCase {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        parse_by_display("spawn case", SpawnCase::iterator(), s)
+    }
+}
+
+// The text between before and after parsed as the parameter of a rule, None if the text does not
+// have that wording
+pub(crate) fn with_param<T: FromStr<Err = ParseError>, R>(
+    text: &str,
+    before: &str,
+    after: &str,
+    rule: impl FnOnce(T) -> R,
+) -> Option<Result<R, ParseError>> {
+    let param = text.strip_prefix(before)?.strip_suffix(after)?;
+    Some(param.parse::<T>().map(rule))
+}
+
+pub(crate) fn with_number<R>(
+    text: &str,
+    before: &str,
+    after: &str,
+    rule: impl FnOnce(u64) -> R,
+) -> Option<Result<R, ParseError>> {
+    let param = text.strip_prefix(before)?.strip_suffix(after)?;
+    Some(
+        param
+            .parse::<u64>()
+            .map(rule)
+            .map_err(|_| ParseError::UnknownValue("number", param.to_string())),
+    )
+}
+
+// A percentage from 0 to 100
+fn with_percentage<R>(
+    text: &str,
+    before: &str,
+    after: &str,
+    rule: impl FnOnce(u64) -> R,
+) -> Option<Result<R, ParseError>> {
+    let param = text.strip_prefix(before)?.strip_suffix(after)?;
+    Some(match param.parse::<u64>() {
+        Ok(percentage) if percentage <= 100 => Ok(rule(percentage)),
+        Ok(_) => Err(ParseError::OutOfRange("percentage", param.to_string())),
+        Err(_) => Err(ParseError::UnknownValue("percentage", param.to_string())),
+    })
+}
+
+// "<prefix> <move> <rest>", None if the text does not start that way
+fn split_rule<'a>(prefix: &str, s: &'a str) -> Option<(Move, &'a str)> {
+    let (direction, rest) = s.strip_prefix(prefix)?.split_once(' ')?;
+    Some((direction.parse::<Move>().ok()?, rest))
+}
+
+impl FromStr for BanMove {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let unknown_rule = || ParseError::UnknownValue("ban rule", s.to_string());
+        if let Some(direction) = s.strip_prefix("always ban move ") {
+            return direction
+                .parse::<Move>()
+                .map(BanMove::Always)
+                .map_err(|_| unknown_rule());
+        }
+        let (d, rest) = split_rule("ban move ", s).ok_or_else(unknown_rule)?;
+        match rest {
+            "if seperates 2 largest tiles" => return Ok(BanMove::Seperates2LargestTiles(d)),
+            "if removes potential merge" => return Ok(BanMove::RemovesPotentialMerge(d)),
+            _ => (),
+        }
+        None.or_else(|| with_param(rest, "when ", "", |c| BanMove::When(d, c)))
+            .or_else(|| {
+                with_param(rest, "if ", " column not locked", |c| {
+                    BanMove::IfColumnNotLocked(d, c)
+                })
+            })
+            .or_else(|| {
+                with_param(rest, "if ", " row not locked", |r| {
+                    BanMove::IfRowNotLocked(d, r)
+                })
+            })
+            .or_else(|| {
+                with_param(rest, "if breaks monotonicity of ", " column", |c| {
+                    BanMove::IfBreaksMonotonicityOfColumn(d, c)
+                })
+            })
+            .or_else(|| {
+                with_param(rest, "if breaks monotonicity of ", " row", |r| {
+                    BanMove::IfBreaksMonotonicityOfRow(d, r)
+                })
+            })
+            .or_else(|| {
+                with_param(rest, "if unlocks ", " column", |c| {
+                    BanMove::UnlocksColumn(d, c)
+                })
+            })
+            .or_else(|| with_param(rest, "if unlocks ", " row", |r| BanMove::UnlocksRow(d, r)))
+            .or_else(|| {
+                with_param(rest, "if moves largest tile out of ", " corner", |c| {
+                    BanMove::MovesLargestTileOutOfCorner(d, c)
+                })
+            })
+            .or_else(|| with_param(rest, "if fills ", " column", |c| BanMove::FillsColumn(d, c)))
+            .or_else(|| with_param(rest, "if fills ", " row", |r| BanMove::FillsRow(d, r)))
+            .or_else(|| {
+                with_param(rest, "if breaks snake chain from ", " corner", |c| {
+                    BanMove::BreaksSnakeChain(d, c)
+                })
+            })
+            .or_else(|| {
+                let (corner, spawn_case) = rest
+                    .strip_prefix("if every next move loses ")?
+                    .split_once(" corner in ")?;
+                Some(corner.parse::<Corner>().and_then(|corner| {
+                    spawn_case
+                        .parse::<SpawnCase>()
+                        .map(|spawn_case| BanMove::EveryNextMoveLosesCorner(d, corner, spawn_case))
+                }))
+            })
+            .or_else(|| {
+                with_param(rest, "if spawn can land in ", " corner", |c| {
+                    BanMove::IfSpawnCanLandInCorner(d, c)
+                })
+            })
+            .or_else(|| {
+                with_percentage(rest, "if chance of no moves above ", "%", |n| {
+                    BanMove::IfGameOverChanceAbove(d, n)
+                })
+            })
+            .or_else(|| {
+                with_number(rest, "if at least ", " empty tiles", |n| {
+                    BanMove::IfEmptyTilesAtLeast(d, n)
+                })
+            })
+            .or_else(|| {
+                with_number(rest, "if at most ", " unique tiles", |n| {
+                    BanMove::IfUniqueTilesAtMost(d, n)
+                })
+            })
+            .or_else(|| {
+                with_param(rest, "if breaks sum order towards ", "", |m| {
+                    BanMove::BreaksSumOrder(d, m)
+                })
+            })
+            .unwrap_or_else(|| Err(unknown_rule()))
+    }
+}
+
+impl FromStr for TryMove {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let unknown_rule = || ParseError::UnknownValue("try rule", s.to_string());
+        if let Some(direction) = s.strip_prefix("always try move ") {
+            return direction
+                .parse::<Move>()
+                .map(TryMove::Always)
+                .map_err(|_| unknown_rule());
+        }
+        let (d, rest) = split_rule("try move ", s).ok_or_else(unknown_rule)?;
+        match rest {
+            "if produces merge" => return Ok(TryMove::ProducesMerge(d)),
+            "if merge possible" => return Ok(TryMove::IfMergePossible(d)),
+            "if makes 2 largest tiles adjacent" => {
+                return Ok(TryMove::Makes2LargestTilesAdjacent(d))
+            }
+            _ => (),
+        }
+        None.or_else(|| with_param(rest, "when ", "", |c| TryMove::When(d, c)))
+            .or_else(|| {
+                with_param(rest, "if moves largest tile to ", " corner", |c| {
+                    TryMove::IfMovesLargestTileToCorner(d, c)
+                })
+            })
+            .or_else(|| {
+                with_param(rest, "if creates monotonic ", " column", |c| {
+                    TryMove::CreatesMonotonicColumn(d, c)
+                })
+            })
+            .or_else(|| {
+                with_param(rest, "if creates monotonic ", " row", |r| {
+                    TryMove::CreatesMonotonicRow(d, r)
+                })
+            })
+            .or_else(|| with_param(rest, "if locks ", " column", |c| TryMove::LocksColumn(d, c)))
+            .or_else(|| with_param(rest, "if locks ", " row", |r| TryMove::LocksRow(d, r)))
+            .or_else(|| {
+                with_param(rest, "if ", " column locked", |c| {
+                    TryMove::ColumnLocked(d, c)
+                })
+            })
+            .or_else(|| with_param(rest, "if ", " row locked", |r| TryMove::RowLocked(d, r)))
+            .or_else(|| {
+                with_param(rest, "if empties ", " column", |c| {
+                    TryMove::EmptiesColumn(d, c)
+                })
+            })
+            .or_else(|| with_param(rest, "if empties ", " row", |r| TryMove::EmptiesRow(d, r)))
+            .or_else(|| {
+                with_param(rest, "if extends snake chain from ", " corner", |c| {
+                    TryMove::ExtendsSnakeChain(d, c)
+                })
+            })
+            .or_else(|| {
+                with_param(rest, "if improves gradient towards ", " corner", |c| {
+                    TryMove::ImprovesGradient(d, c)
+                })
+            })
+            .or_else(|| {
+                with_param(rest, "if enables merge next move in ", "", |c| {
+                    TryMove::EnablesMergeNextMove(d, c)
+                })
+            })
+            .or_else(|| {
+                with_number(rest, "if at most ", " empty tiles", |n| {
+                    TryMove::IfEmptyTilesAtMost(d, n)
+                })
+            })
+            .or_else(|| {
+                with_number(rest, "if at least ", " unique tiles", |n| {
+                    TryMove::IfUniqueTilesAtLeast(d, n)
+                })
+            })
+            .or_else(|| {
+                with_number(rest, "if reduces tiles of at least ", "", |n| {
+                    TryMove::ReducesTilesAtLeast(d, n)
+                })
+            })
+            .or_else(|| {
+                with_param(rest, "if creates sum order towards ", "", |m| {
+                    TryMove::CreatesSumOrder(d, m)
+                })
+            })
+            .unwrap_or_else(|| Err(unknown_rule()))
+    }
+}
+
+impl FromStr for Strategy {
+    type Err = ParseError;
+
+    // "Ban Rules: rule->rule\tTry Rules: rule->rule\tFallback: move->move", optionally followed by
+    // "\tFallback Preferences: preference->preference" and "\tAll Banned: policy"
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut sections = s.trim().split('\t');
+        let ban_rules: BanRules = parse_section(sections.next(), "Ban Rules:", "ban rule")?;
+        let try_rules: TryRules = parse_section(sections.next(), "Try Rules:", "try rule")?;
+        let fallback_moves: Vec<Move> =
+            parse_section(sections.next(), "Fallback:", "fallback move")?;
+        let mut strategy = Strategy::new(&ban_rules, &try_rules, &fallback_moves)
+            .ok_or_else(|| ParseError::InvalidStrategy(s.trim().to_string()))?;
+        for section in sections {
+            if section.starts_with("Fallback Preferences:") {
+                strategy = strategy.with_fallback_preferences(parse_section(
+                    Some(section),
+                    "Fallback Preferences:",
+                    "fallback preference",
+                )?);
+            } else if let Some(policy) = section.strip_prefix("All Banned:") {
+                strategy = strategy.with_banned_policy(policy.parse::<BannedPolicy>()?);
+            } else {
+                return Err(ParseError::UnknownValue("section", section.to_string()));
+            }
+        }
+        Ok(strategy)
+    }
+}
+
+pub fn parse_strategy_file(contents: &str) -> Result<Strategy, ParseError> {
+    let mut ban_rules = Vec::new();
+    let mut try_rules = Vec::new();
+    let mut fallback_moves = None;
+    let mut fallback_preferences = Vec::new();
+    let mut banned_policy = BannedPolicy::default();
+    for (line_idx, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let invalid_line = |error| ParseError::InvalidItem("line", line_idx + 1, Box::new(error));
+        if let Some(moves) = line.strip_prefix("fallback") {
+            fallback_moves = Some(
+                parse_section::<Move>(Some(moves), "", "fallback move").map_err(invalid_line)?,
+            );
+        } else if line.starts_with("prefer ") {
+            fallback_preferences.push(line.parse::<FallbackPreference>().map_err(invalid_line)?);
+        } else if let Some(policy) = line.strip_prefix("all banned ") {
+            banned_policy = policy.parse::<BannedPolicy>().map_err(invalid_line)?;
+        } else {
+            // errors in a condition are reported as they are, anything else is an unknown rule
+            match line.parse::<BanMove>() {
+                Ok(ban_rule) => ban_rules.push(ban_rule),
+                Err(ParseError::UnknownValue("ban rule", _)) => match line.parse::<TryMove>() {
+                    Ok(try_rule) => try_rules.push(try_rule),
+                    Err(ParseError::UnknownValue("try rule", _)) => {
+                        return Err(invalid_line(ParseError::UnknownValue(
+                            "rule",
+                            line.to_string(),
+                        )))
+                    }
+                    Err(error) => return Err(invalid_line(error)),
+                },
+                Err(error) => return Err(invalid_line(error)),
+            }
+        }
+    }
+    let fallback_moves = fallback_moves.ok_or(ParseError::MissingSection("fallback"))?;
+    Strategy::new(&ban_rules, &try_rules, &fallback_moves)
+        .map(|strategy| {
+            strategy
+                .with_fallback_preferences(fallback_preferences)
+                .with_banned_policy(banned_policy)
+        })
+        .ok_or_else(|| ParseError::InvalidStrategy(contents.trim().to_string()))
+}
+
+pub fn to_strategy_file(strategy: &Strategy) -> String {
+    let mut contents = String::new();
+    for ban_rule in &strategy.ban_rules {
+        contents.push_str(&format!("{}\n", ban_rule));
+    }
+    for try_rule in &strategy.try_rules {
+        contents.push_str(&format!("{}\n", try_rule));
+    }
+    let fallback_moves = strategy
+        .fallback_moves
+        .iter()
+        .map(|direction| direction.to_string())
+        .collect::<Vec<_>>();
+    contents.push_str(&format!("fallback {}\n", fallback_moves.join("->")));
+    for preference in strategy.get_fallback_preferences() {
+        contents.push_str(&format!("{}\n", preference));
+    }
+    if strategy.get_banned_policy() != BannedPolicy::default() {
+        contents.push_str(&format!("all banned {}\n", strategy.get_banned_policy()));
+    }
+    contents
+}
+
+pub fn load_strategy(filename: &str) -> io::Result<Strategy> {
+    parse_strategy_file(&fs::read_to_string(filename)?)
+        .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error.to_string()))
+}
+
+pub fn save_strategy(strategy: &Strategy, filename: &str) -> io::Result<()> {
+    fs::write(filename, to_strategy_file(strategy))
+}
+
+fn parse_section<T: FromStr<Err = ParseError>>(
+    section: Option<&str>,
+    prefix: &'static str,
+    item_name: &'static str,
+) -> Result<Vec<T>, ParseError> {
+    let items = section
+        .and_then(|section| section.trim().strip_prefix(prefix))
+        .ok_or(ParseError::MissingSection(prefix))?
+        .trim();
+    if items.is_empty() {
+        return Ok(Vec::new());
+    }
+    items
+        .split("->")
+        .enumerate()
+        .map(|(idx, item)| {
+            item.parse::<T>()
+                .map_err(|error| ParseError::InvalidItem(item_name, idx + 1, Box::new(error)))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::condition::{Predicate, When};
+    use super::super::SNAKE;
+    use super::*;
+
+    fn with_move<T>(rule: fn(Move) -> T) -> Vec<T> {
+        Move::iterator().map(rule).collect()
+    }
+
+    fn with_move_column<T>(rule: fn(Move, Column) -> T) -> Vec<T> {
+        Move::iterator()
+            .flat_map(|direction| Column::iterator().map(move |column| rule(direction, column)))
+            .collect()
+    }
+
+    fn with_move_row<T>(rule: fn(Move, Row) -> T) -> Vec<T> {
+        Move::iterator()
+            .flat_map(|direction| Row::iterator().map(move |row| rule(direction, row)))
+            .collect()
+    }
+
+    fn with_move_corner<T>(rule: fn(Move, Corner) -> T) -> Vec<T> {
+        Move::iterator()
+            .flat_map(|direction| Corner::iterator().map(move |corner| rule(direction, corner)))
+            .collect()
+    }
+
+    fn with_move_threshold<T>(
+        rule: fn(Move, u64) -> T,
+        thresholds: impl Iterator<Item = u64> + Clone,
+    ) -> Vec<T> {
+        Move::iterator()
+            .flat_map(|direction| {
+                thresholds
+                    .clone()
+                    .map(move |threshold| rule(direction, threshold))
+            })
+            .collect()
+    }
+
+    fn with_move_spawn_case<T>(rule: fn(Move, SpawnCase) -> T) -> Vec<T> {
+        Move::iterator()
+            .flat_map(|direction| SpawnCase::iterator().map(move |case| rule(direction, case)))
+            .collect()
+    }
+
+    fn with_move_corner_spawn_case<T>(rule: fn(Move, Corner, SpawnCase) -> T) -> Vec<T> {
+        Move::iterator()
+            .flat_map(|direction| {
+                Corner::iterator().flat_map(move |corner| {
+                    SpawnCase::iterator().map(move |case| rule(direction, corner, case))
+                })
+            })
+            .collect()
+    }
+
+    fn with_move_move<T>(rule: fn(Move, Move) -> T) -> Vec<T> {
+        Move::iterator()
+            .flat_map(|direction| Move::iterator().map(move |other| rule(direction, other)))
+            .collect()
+    }
+
+    // Tile counts and tile values to check, not just the thresholds used in the search
+    fn tile_counts() -> impl Iterator<Item = u64> + Clone {
+        0..=16
+    }
+
+    fn tile_values() -> impl Iterator<Item = u64> + Clone {
+        (1..16).map(|rank| 2_u64.pow(rank))
+    }
+
+    // Every kind of ban rule with a range of parameters
+    fn all_ban_rules() -> Vec<BanMove> {
+        let mut rules = Vec::new();
+        rules.append(&mut with_move(BanMove::Always));
+        rules.append(&mut with_move_column(BanMove::IfColumnNotLocked));
+        rules.append(&mut with_move_row(BanMove::IfRowNotLocked));
+        rules.append(&mut with_move_column(BanMove::IfBreaksMonotonicityOfColumn));
+        rules.append(&mut with_move_row(BanMove::IfBreaksMonotonicityOfRow));
+        rules.append(&mut with_move(BanMove::Seperates2LargestTiles));
+        rules.append(&mut with_move_column(BanMove::UnlocksColumn));
+        rules.append(&mut with_move_row(BanMove::UnlocksRow));
+        rules.append(&mut with_move(BanMove::RemovesPotentialMerge));
+        rules.append(&mut with_move_corner(BanMove::MovesLargestTileOutOfCorner));
+        rules.append(&mut with_move_column(BanMove::FillsColumn));
+        rules.append(&mut with_move_row(BanMove::FillsRow));
+        rules.append(&mut with_move_corner(BanMove::BreaksSnakeChain));
+        rules.append(&mut with_move_corner_spawn_case(
+            BanMove::EveryNextMoveLosesCorner,
+        ));
+        rules.append(&mut with_move_corner(BanMove::IfSpawnCanLandInCorner));
+        rules.append(&mut with_move_threshold(
+            BanMove::IfGameOverChanceAbove,
+            0..=100,
+        ));
+        rules.append(&mut with_move_threshold(
+            BanMove::IfEmptyTilesAtLeast,
+            tile_counts(),
+        ));
+        rules.append(&mut with_move_threshold(
+            BanMove::IfUniqueTilesAtMost,
+            tile_counts(),
+        ));
+        rules.append(&mut with_move_move(BanMove::BreaksSumOrder));
+        rules
+    }
+
+    // Every kind of try rule with a range of parameters
+    fn all_try_rules() -> Vec<TryMove> {
+        let mut rules = Vec::new();
+        rules.append(&mut with_move(TryMove::Always));
+        rules.append(&mut with_move(TryMove::ProducesMerge));
+        rules.append(&mut with_move(TryMove::IfMergePossible));
+        rules.append(&mut with_move_corner(TryMove::IfMovesLargestTileToCorner));
+        rules.append(&mut with_move(TryMove::Makes2LargestTilesAdjacent));
+        rules.append(&mut with_move_column(TryMove::CreatesMonotonicColumn));
+        rules.append(&mut with_move_row(TryMove::CreatesMonotonicRow));
+        rules.append(&mut with_move_column(TryMove::LocksColumn));
+        rules.append(&mut with_move_row(TryMove::LocksRow));
+        rules.append(&mut with_move_column(TryMove::ColumnLocked));
+        rules.append(&mut with_move_row(TryMove::RowLocked));
+        rules.append(&mut with_move_column(TryMove::EmptiesColumn));
+        rules.append(&mut with_move_row(TryMove::EmptiesRow));
+        rules.append(&mut with_move_corner(TryMove::ExtendsSnakeChain));
+        rules.append(&mut with_move_corner(TryMove::ImprovesGradient));
+        rules.append(&mut with_move_spawn_case(TryMove::EnablesMergeNextMove));
+        rules.append(&mut with_move_threshold(
+            TryMove::IfEmptyTilesAtMost,
+            tile_counts(),
+        ));
+        rules.append(&mut with_move_threshold(
+            TryMove::IfUniqueTilesAtLeast,
+            tile_counts(),
+        ));
+        rules.append(&mut with_move_threshold(
+            TryMove::ReducesTilesAtLeast,
+            tile_values(),
+        ));
+        rules.append(&mut with_move_move(TryMove::CreatesSumOrder));
+        rules
+    }
+
+    #[test]
+    fn it_rules_round_trip() {
+        for rule in all_ban_rules() {
+            assert_eq!(rule.to_string().parse::<BanMove>(), Ok(rule));
+        }
+        for rule in all_try_rules() {
+            assert_eq!(rule.to_string().parse::<TryMove>(), Ok(rule));
+        }
+        // parameters are parsed as numbers, so values no board can reach still read back
+        for rule in [
+            BanMove::IfEmptyTilesAtLeast(Move::Left, 20),
+            BanMove::IfUniqueTilesAtMost(Move::Down, 1000),
+        ] {
+            assert_eq!(rule.to_string().parse::<BanMove>(), Ok(rule));
+        }
+        for rule in [
+            TryMove::ReducesTilesAtLeast(Move::Up, 100),
+            TryMove::IfEmptyTilesAtMost(Move::Right, 17),
+        ] {
+            assert_eq!(rule.to_string().parse::<TryMove>(), Ok(rule));
+        }
+        assert_eq!(
+            "ban move left if at least many empty tiles".parse::<BanMove>(),
+            Err(ParseError::UnknownValue("number", "many".to_string()))
+        );
+        assert_eq!(
+            "ban move up if chance of no moves above 150%".parse::<BanMove>(),
+            Err(ParseError::OutOfRange("percentage", "150".to_string()))
+        );
+        // a strategy can not be built with a rule that would not read back
+        assert!(!BanMove::IfGameOverChanceAbove(Move::Up, 150).is_valid());
+        assert_eq!(
+            Strategy::new(
+                &vec![BanMove::IfGameOverChanceAbove(Move::Up, 150)],
+                &vec![],
+                &vec![Move::Left],
+            ),
+            None
+        );
+        // the rules generated for the search must all be parseable
+        for rule in BanMove::generate_all_variations() {
+            assert_eq!(rule.to_string().parse::<BanMove>(), Ok(rule));
+        }
+        for rule in TryMove::generate_all_variations() {
+            assert_eq!(rule.to_string().parse::<TryMove>(), Ok(rule));
+        }
+        for rule in BanMove::generate_condition_variations(1) {
+            assert_eq!(rule.to_string().parse::<BanMove>(), Ok(rule));
+        }
+        for rule in TryMove::generate_condition_variations(2)
+            .into_iter()
+            .step_by(97)
+        {
+            assert_eq!(rule.to_string().parse::<TryMove>(), Ok(rule));
+        }
+    }
+
+    #[test]
+    fn it_parse_conditions() {
+        let rule = "try move left when bottom row locked after and not vertical merge possible before and vertical merge possible after";
+        assert_eq!(
+            rule.parse::<TryMove>(),
+            Ok(TryMove::When(
+                Move::Left,
+                Condition::all(&[
+                    Term::new(Predicate::RowLocked(Row::Bottom), When::After),
+                    Term::not(Predicate::VerticalMergePossible, When::Before),
+                    Term::new(Predicate::VerticalMergePossible, When::After),
+                ])
+                .unwrap()
+            ))
+        );
+        assert_eq!(
+            "ban move up when left column locked before or left column empty after"
+                .parse::<BanMove>()
+                .map(|rule| rule.to_string()),
+            Ok("ban move up when left column locked before or left column empty after".to_string())
+        );
+        assert_eq!(
+            "left column locked before and right row empty after".parse::<Condition>(),
+            Err(ParseError::InvalidItem(
+                "term",
+                2,
+                Box::new(ParseError::UnknownValue(
+                    "term",
+                    "right row empty after".to_string()
+                ))
+            ))
+        );
+        // and binds tighter than or
+        assert_eq!(
+            "left column locked before and left column empty after or 2 largest tiles adjacent before"
+                .parse::<Condition>()
+                .map(|condition| condition.to_string()),
+            Ok("(left column locked before and left column empty after) or 2 largest tiles adjacent before".to_string())
+        );
+        let grouped = "left column locked before and (left column empty after or 2 largest tiles adjacent before)";
+        assert_eq!(
+            grouped
+                .parse::<Condition>()
+                .map(|condition| condition.to_string()),
+            Ok(grouped.to_string())
+        );
+        assert!("left column locked before and ((left column empty after))"
+            .parse::<Condition>()
+            .is_err());
+        assert!("(left column locked before and left column empty after"
+            .parse::<Condition>()
+            .is_err());
+        assert_eq!(
+            parse_strategy_file("try move up when merge possible\nfallback left\n"),
+            Err(ParseError::InvalidItem(
+                "line",
+                1,
+                Box::new(ParseError::InvalidItem(
+                    "term",
+                    1,
+                    Box::new(ParseError::UnknownValue(
+                        "term",
+                        "merge possible".to_string()
+                    ))
+                ))
+            ))
+        );
+    }
+
+    #[test]
+    fn it_strategy_round_trip() {
+        let strategy = Strategy::new(
+            &vec![BanMove::IfColumnNotLocked(Move::Up, Column::Left)],
+            &vec![
+                TryMove::IfMovesLargestTileToCorner(Move::Left, Corner::BottomLeft),
+                TryMove::ProducesMerge(Move::Up),
+                TryMove::ProducesMerge(Move::Down),
+                TryMove::CreatesMonotonicRow(Move::Down, Row::MiddleTop),
+            ],
+            &vec![Move::Left, Move::Up, Move::Down, Move::Right],
+        )
+        .unwrap();
+        assert_eq!(strategy.to_string().parse::<Strategy>(), Ok(strategy));
+
+        let no_rules = Strategy::new(&vec![], &vec![], &vec![Move::Left, Move::Up]).unwrap();
+        assert_eq!(no_rules.to_string().parse::<Strategy>(), Ok(no_rules));
+
+        let fallback_config = parse_strategy_file(&format!(
+            "{}prefer right over up when left column locked before\n\
+             all banned least harmful by snake chain from bottom left corner\n",
+            SNAKE
+        ))
+        .unwrap();
+        assert_eq!(fallback_config.get_fallback_preferences().len(), 1);
+        assert_eq!(
+            fallback_config.to_string().parse::<Strategy>(),
+            Ok(fallback_config.clone())
+        );
+        assert_eq!(
+            parse_strategy_file(&to_strategy_file(&fallback_config)),
+            Ok(fallback_config)
+        );
+        for policy in BannedPolicy::generate_all_variations() {
+            assert_eq!(policy.to_string().parse::<BannedPolicy>(), Ok(policy));
+        }
+    }
+
+    #[test]
+    fn it_strategy_file() {
+        let snake = parse_strategy_file(SNAKE).unwrap();
+        assert_eq!(
+            snake,
+            Strategy::new(
+                &vec![BanMove::IfColumnNotLocked(Move::Up, Column::Left)],
+                &vec![
+                    TryMove::IfMovesLargestTileToCorner(Move::Left, Corner::BottomLeft),
+                    TryMove::ProducesMerge(Move::Up),
+                    TryMove::ProducesMerge(Move::Down),
+                    TryMove::CreatesMonotonicRow(Move::Down, Row::MiddleTop),
+                ],
+                &vec![Move::Left, Move::Up, Move::Down, Move::Right]
+            )
+            .unwrap()
+        );
+        assert_eq!(parse_strategy_file(&to_strategy_file(&snake)), Ok(snake));
+        assert_eq!(
+            parse_strategy_file("try move up if produces merge\nfallback left->up\n"),
+            Ok(Strategy::new(
+                &vec![],
+                &vec![TryMove::ProducesMerge(Move::Up)],
+                &vec![Move::Left, Move::Up]
+            )
+            .unwrap())
+        );
+        assert_eq!(
+            parse_strategy_file("# comment\n\ntry move up if merges\nfallback left\n"),
+            Err(ParseError::InvalidItem(
+                "line",
+                3,
+                Box::new(ParseError::UnknownValue(
+                    "rule",
+                    "try move up if merges".to_string()
+                ))
+            ))
+        );
+        assert_eq!(
+            parse_strategy_file("try move up if produces merge"),
+            Err(ParseError::MissingSection("fallback"))
+        );
+    }
+
+    #[test]
+    fn it_parse_errors() {
+        assert_eq!(
+            "Ban Rules: \tTry Rules: try move left if merge possible->try move lft if produces merge\tFallback: up"
+                .parse::<Strategy>(),
+            Err(ParseError::InvalidItem(
+                "try rule",
+                2,
+                Box::new(ParseError::UnknownValue(
+                    "try rule",
+                    "try move lft if produces merge".to_string()
+                ))
+            ))
+        );
+        assert_eq!(
+            "Ban Rules: \tTry Rules: ".parse::<Strategy>(),
+            Err(ParseError::MissingSection("Fallback:"))
+        );
+        assert_eq!(
+            "diagonal".parse::<Move>().unwrap_err().to_string(),
+            "unknown move: \"diagonal\""
+        );
+    }
+}
@@ -0,0 +1,324 @@
+use super::attributes;
+use super::attributes::Corner;
+use super::parse::{with_number, with_param, ParseError};
+use super::search::SearchSpace;
+use super::Strategy;
+use crate::ai::{MoveScores, ScoreMoves, AI};
+use crate::engine;
+use crate::engine::{Board, GameEngine, Move};
+use rand::{seq::IteratorRandom, thread_rng, Rng};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::str::FromStr;
+
+// A strategy made of phases, each with its own rules. The phases are checked in order and the
+// first one whose condition holds plays the move, the default strategy plays when none hold.
+//
+// Written on one line as "Default: strategy | When condition: strategy | ...". Strategy files can
+// have blank lines and lines starting with # around it.
+
+// Mutations stop adding phases once there are this many
+pub const MAX_PHASES: usize = 4;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PhaseCondition {
+    // the value of the largest tile, not its rank
+    MaxTileAtLeast(u64),
+    EmptyTilesAtMost(u64),
+    LargestTileNotInCorner(Corner),
+}
+
+impl PhaseCondition {
+    pub fn holds(&self, board: Board) -> bool {
+        match *self {
+            PhaseCondition::MaxTileAtLeast(tile_value) => {
+                engine::get_highest_tile_val(board) >= tile_value
+            }
+            PhaseCondition::EmptyTilesAtMost(num_empty) => {
+                attributes::count_empty_tiles(board) <= num_empty
+            }
+            PhaseCondition::LargestTileNotInCorner(corner) => {
+                !attributes::is_largest_tile_in_corner(board, corner)
+            }
+        }
+    }
+
+    // The thresholds tried by the search
+    pub fn generate_all_variations() -> Vec<Self> {
+        let mut variations = Vec::new();
+        for &tile_value in [256, 512, 1024, 2048].iter() {
+            variations.push(PhaseCondition::MaxTileAtLeast(tile_value));
+        }
+        for &num_empty in [2, 4, 6].iter() {
+            variations.push(PhaseCondition::EmptyTilesAtMost(num_empty));
+        }
+        for corner in Corner::iterator() {
+            variations.push(PhaseCondition::LargestTileNotInCorner(corner));
+        }
+        variations
+    }
+}
+
+impl fmt::Display for PhaseCondition {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PhaseCondition::MaxTileAtLeast(tile_value) => {
+                write!(f, "max tile at least {}", tile_value)
+            }
+            PhaseCondition::EmptyTilesAtMost(num_empty) => {
+                write!(f, "at most {} empty tiles", num_empty)
+            }
+            PhaseCondition::LargestTileNotInCorner(corner) => {
+                write!(f, "largest tile not in {} corner", corner)
+            }
+        }
+    }
+}
+
+impl FromStr for PhaseCondition {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        None.or_else(|| with_number(s, "max tile at least ", "", PhaseCondition::MaxTileAtLeast))
+            .or_else(|| {
+                with_number(
+                    s,
+                    "at most ",
+                    " empty tiles",
+                    PhaseCondition::EmptyTilesAtMost,
+                )
+            })
+            .or_else(|| {
+                with_param(
+                    s,
+                    "largest tile not in ",
+                    " corner",
+                    PhaseCondition::LargestTileNotInCorner,
+                )
+            })
+            .unwrap_or_else(|| Err(ParseError::UnknownValue("phase condition", s.to_string())))
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Phase {
+    pub condition: PhaseCondition,
+    pub strategy: Strategy,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct PhasedStrategy {
+    default: Strategy,
+    phases: Vec<Phase>,
+}
+
+impl PhasedStrategy {
+    pub fn new(default: Strategy, phases: Vec<Phase>) -> Self {
+        PhasedStrategy { default, phases }
+    }
+
+    pub fn get_default(&self) -> &Strategy {
+        &self.default
+    }
+
+    pub fn get_phases(&self) -> &Vec<Phase> {
+        &self.phases
+    }
+
+    // The strategy that plays on this board
+    pub fn get_strategy(&mut self, board: Board) -> &mut Strategy {
+        match self
+            .phases
+            .iter_mut()
+            .find(|phase| phase.condition.holds(board))
+        {
+            Some(phase) => &mut phase.strategy,
+            None => &mut self.default,
+        }
+    }
+}
+
+impl AI for PhasedStrategy {
+    fn get_next_move<T: GameEngine>(&mut self, engine: &T, board: Board) -> Option<Move> {
+        self.get_strategy(board).get_next_move(engine, board)
+    }
+}
+
+impl ScoreMoves for PhasedStrategy {
+    fn score_moves<T: GameEngine>(&mut self, engine: &T, board: Board) -> MoveScores {
+        self.get_strategy(board).score_moves(engine, board)
+    }
+}
+
+impl SearchSpace for PhasedStrategy {
+    // Adds a phase or removes one one time in six each, otherwise changes the condition of a phase
+    // one time in three or mutates the rules of a phase. An added phase starts from a mutation of
+    // the default strategy with a condition no other phase uses.
+    fn mutate(&self) -> Self {
+        let mut rng = thread_rng();
+        let mut mutated = self.clone();
+        match rng.gen_range(0, 6) {
+            0 if self.phases.len() < MAX_PHASES => {
+                let condition = PhaseCondition::generate_all_variations()
+                    .into_iter()
+                    .filter(|&condition| {
+                        !self.phases.iter().any(|phase| phase.condition == condition)
+                    })
+                    .choose(&mut rng);
+                if let Some(condition) = condition {
+                    let phase_idx = rng.gen_range(0, self.phases.len() + 1);
+                    mutated.phases.insert(
+                        phase_idx,
+                        Phase {
+                            condition,
+                            strategy: self.default.mutate(),
+                        },
+                    );
+                    return mutated;
+                }
+            }
+            1 if !self.phases.is_empty() => {
+                mutated.phases.remove(rng.gen_range(0, self.phases.len()));
+                return mutated;
+            }
+            _ => (),
+        }
+        let phase_idx = rng.gen_range(0, self.phases.len() + 1);
+        if phase_idx == self.phases.len() {
+            mutated.default = self.default.mutate();
+        } else if rng.gen_range(0, 3) == 0 {
+            let phase = &mut mutated.phases[phase_idx];
+            phase.condition = PhaseCondition::generate_all_variations()
+                .into_iter()
+                .filter(|&condition| condition != phase.condition)
+                .choose(&mut rng)
+                .expect("failed to select new phase condition");
+        } else {
+            let phase = &mut mutated.phases[phase_idx];
+            phase.strategy = phase.strategy.mutate();
+        }
+        mutated
+    }
+}
+
+impl fmt::Display for PhasedStrategy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Default: {}", self.default)?;
+        for phase in &self.phases {
+            write!(f, " | When {}: {}", phase.condition, phase.strategy)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for PhasedStrategy {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut sections = s.trim().split(" | ");
+        let default = sections
+            .next()
+            .and_then(|section| section.strip_prefix("Default: "))
+            .ok_or(ParseError::MissingSection("Default:"))?
+            .parse::<Strategy>()?;
+        let phases = sections
+            .enumerate()
+            .map(|(idx, section)| {
+                let invalid_phase =
+                    |error| ParseError::InvalidItem("phase", idx + 1, Box::new(error));
+                let (condition, strategy) = section
+                    .strip_prefix("When ")
+                    .and_then(|phase| phase.split_once(": "))
+                    .ok_or_else(|| {
+                        invalid_phase(ParseError::UnknownValue("phase", section.to_string()))
+                    })?;
+                Ok(Phase {
+                    condition: condition.parse::<PhaseCondition>().map_err(invalid_phase)?,
+                    strategy: strategy.parse::<Strategy>().map_err(invalid_phase)?,
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(PhasedStrategy::new(default, phases))
+    }
+}
+
+pub fn parse_phased_strategy_file(text: &str) -> Result<PhasedStrategy, ParseError> {
+    text.lines()
+        .filter(|line| !line.trim().is_empty() && !line.trim_start().starts_with('#'))
+        .collect::<Vec<_>>()
+        .join(" ")
+        .parse::<PhasedStrategy>()
+}
+
+pub fn load_phased_strategy(filename: &str) -> io::Result<PhasedStrategy> {
+    parse_phased_strategy_file(&fs::read_to_string(filename)?)
+        .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::try_rules::TryMove;
+    use super::*;
+    use crate::engine::GameEngineNoStores;
+
+    #[test]
+    fn it_phased_strategy() {
+        let engine = GameEngineNoStores;
+        let early = Strategy::new(
+            &vec![],
+            &vec![TryMove::ProducesMerge(Move::Up)],
+            &vec![Move::Left, Move::Down],
+        )
+        .unwrap();
+        let late = Strategy::new(&vec![], &vec![], &vec![Move::Up, Move::Left]).unwrap();
+        let mut phased = PhasedStrategy::new(
+            early,
+            vec![Phase {
+                condition: PhaseCondition::MaxTileAtLeast(512),
+                strategy: late,
+            }],
+        );
+        assert_eq!(
+            phased.get_next_move(&engine, 0x0000000000000012),
+            Some(Move::Left)
+        );
+        assert_eq!(
+            phased.get_next_move(&engine, 0x0000000000000092),
+            Some(Move::Up)
+        );
+        assert_eq!(
+            phased.to_string().parse::<PhasedStrategy>(),
+            Ok(phased.clone())
+        );
+        let mut phase_counts = Vec::new();
+        for _ in 0..100 {
+            let mutated = phased.mutate();
+            phase_counts.push(mutated.get_phases().len());
+            assert_eq!(mutated.to_string().parse::<PhasedStrategy>(), Ok(mutated));
+        }
+        // phases are added and removed as well as changed
+        assert!(phase_counts.contains(&0));
+        assert!(phase_counts.contains(&1));
+        assert!(phase_counts.contains(&2));
+        let mut grown = phased.clone();
+        for _ in 0..1000 {
+            grown = grown.mutate();
+            assert!(grown.get_phases().len() <= MAX_PHASES);
+        }
+        let bundled =
+            parse_phased_strategy_file(include_str!("../../../strategies/phased_snake.txt"))
+                .unwrap();
+        assert_eq!(bundled.get_default(), &super::super::snake());
+        assert_eq!(
+            bundled.get_phases()[0].condition,
+            PhaseCondition::LargestTileNotInCorner(Corner::BottomLeft)
+        );
+        assert!(
+            "When max tile at least 512: Ban Rules: \tTry Rules: \tFallback: left"
+                .parse::<PhasedStrategy>()
+                .is_err()
+        );
+    }
+}
@@ -1,10 +1,10 @@
 use super::{
-    average, median, print_best_strategy_info, strategy_duel, Runs, StrategyData,
+    average, median, print_best_strategy_info, strategy_duel, Runs, SearchSpace, StrategyData,
     StrategyDuelResult,
 };
-use crate::ai::strategy::{ban_rules::BanMove, mann_whitney::Confidence, try_rules::TryMove, Rule};
+use crate::ai::strategy::{mann_whitney::Confidence, try_rules::TryMove};
 use crate::engine::GameEngine;
-use rand::{seq::IteratorRandom, thread_rng};
+use rand::{seq::SliceRandom, thread_rng};
 use std::fs::File;
 use std::io::Write;
 use std::path::Path;
@@ -17,6 +17,7 @@ pub fn ils_mutate_try_always_accept<T: GameEngine>(
     iterated_local_search(
         engine,
         strategy_data,
+        super::local::local_search_ban_restart,
         permutate_try_only,
         ILSVariation::AlwaysAccept,
     )
@@ -29,6 +30,7 @@ pub fn ils_mutate_any_always_accept<T: GameEngine>(
     iterated_local_search(
         engine,
         strategy_data,
+        super::local::local_search_ban_restart,
         permutate_any,
         ILSVariation::AlwaysAccept,
     )
@@ -41,6 +43,7 @@ pub fn ils_mutate_try_accept_if_better<T: GameEngine>(
     iterated_local_search(
         engine,
         strategy_data,
+        super::local::local_search_ban_restart,
         permutate_try_only,
         ILSVariation::OnlyAcceptIfBetter,
     )
@@ -53,19 +56,39 @@ pub fn ils_mutate_any_accept_if_better<T: GameEngine>(
     iterated_local_search(
         engine,
         strategy_data,
+        super::local::local_search_ban_restart,
         permutate_any,
         ILSVariation::OnlyAcceptIfBetter,
     )
 }
 
-fn iterated_local_search<T: GameEngine>(
+// Mutations can also pick try rules written in the condition language, the variations are only
+// built once as there are tens of thousands of them
+pub fn ils_mutate_try_conditions_accept_if_better<T: GameEngine>(
     engine: &T,
     strategy_data: StrategyData,
-    mutation_fn: fn(&StrategyData) -> StrategyData,
-    variation: ILSVariation,
 ) -> StrategyData {
+    let mut variations = TryMove::generate_all_variations();
+    variations.append(&mut TryMove::generate_condition_variations(2));
+    iterated_local_search(
+        engine,
+        strategy_data,
+        super::local::local_search_ban_restart,
+        |current_best| permutate_try(current_best, &variations),
+        ILSVariation::OnlyAcceptIfBetter,
+    )
+}
+
+// Works on any kind of strategy, the local search climbs to a local maxima after each mutation
+pub(super) fn iterated_local_search<T: GameEngine, S: SearchSpace>(
+    engine: &T,
+    strategy_data: StrategyData<S>,
+    local_search_fn: impl Fn(&T, StrategyData<S>) -> StrategyData<S>,
+    mutation_fn: impl Fn(&StrategyData<S>) -> StrategyData<S>,
+    variation: ILSVariation,
+) -> StrategyData<S> {
     println!("\n\nStarting ILS...");
-    let mut global_best = super::local::local_search_ban_restart(engine, strategy_data);
+    let mut global_best = local_search_fn(engine, strategy_data);
     let mut current_best = global_best.clone();
     let max_count = 20;
     for count in 0..max_count {
@@ -73,7 +96,7 @@ fn iterated_local_search<T: GameEngine>(
         let mutated = mutation_fn(&current_best);
         println!("\nCurrent best:\n{}", current_best.strategy);
         println!("\nMutated:\n{}", mutated.strategy);
-        let mut mutated_best = super::local::local_search_ban_restart(engine, mutated);
+        let mut mutated_best = local_search_fn(engine, mutated);
         match variation {
             ILSVariation::AlwaysAccept => {
                 current_best = mutated_best;
@@ -279,101 +302,51 @@ fn iterated_local_search_save<T: GameEngine>(
     }
 }
 #[derive(PartialEq)]
-enum ILSVariation {
+pub(super) enum ILSVariation {
     AlwaysAccept,
     OnlyAcceptIfBetter,
 }
 
 fn permutate_try_only(current_best: &StrategyData) -> StrategyData {
-    if current_best.strategy.try_rules.len() == 0 {
+    permutate_try(current_best, &TryMove::generate_all_variations())
+}
+
+fn permutate_try(current_best: &StrategyData, variations: &[TryMove]) -> StrategyData {
+    let try_rules = &current_best.strategy.try_rules;
+    if try_rules.is_empty() {
         return current_best.to_owned();
     }
-    let mut mutated = current_best.clone();
     let mut rng = thread_rng();
-    // select random rule
-    let rule_to_change = current_best
-        .clone()
-        .strategy
-        .try_rules
-        .into_iter()
+    let rule_to_change = *try_rules
         .choose(&mut rng)
         .expect("could not pick random rule");
 
+    // draw again when the rule is unusable instead of filtering, there can be tens of thousands
+    // of variations
     loop {
-        let new_rule = TryMove::generate_all_variations()
-            .into_iter()
-            .filter(|&rule| {
-                rule != rule_to_change && !current_best.clone().strategy.try_rules.contains(&rule)
-            })
+        let new_rule = *variations
             .choose(&mut rng)
             .expect("failed to select new rule");
-        match mutated.strategy.swap_try_rule(rule_to_change, new_rule) {
-            Some(valid_strategy) => {
-                mutated.strategy = valid_strategy;
-                mutated.results = Vec::new();
-                return mutated;
-            }
-            None => (),
+        if new_rule == rule_to_change || try_rules.contains(&new_rule) {
+            continue;
+        }
+        if let Some(valid_strategy) = current_best
+            .strategy
+            .swap_try_rule(rule_to_change, new_rule)
+        {
+            return StrategyData::new(valid_strategy);
         }
     }
 }
 
-fn permutate_any(current_best: &StrategyData) -> StrategyData {
-    let mut mutated = current_best.clone();
-    let mut rng = thread_rng();
-    // select random rule
-    let rule_to_change = current_best
-        .clone()
-        .strategy
-        .get_rules_ban_first()
-        .into_iter()
-        .choose(&mut rng)
-        .expect("could not pick random rule");
-
-    loop {
-        match rule_to_change {
-            Rule::Try(try_rule) => {
-                let new_rule = TryMove::generate_all_variations()
-                    .into_iter()
-                    .filter(|&rule| {
-                        rule != try_rule && !current_best.clone().strategy.try_rules.contains(&rule)
-                    })
-                    .choose(&mut rng)
-                    .expect("failed to select new rule");
-                match mutated.strategy.swap_try_rule(try_rule, new_rule) {
-                    Some(valid_strategy) => {
-                        mutated.strategy = valid_strategy;
-                        mutated.results = Vec::new();
-                        return mutated;
-                    }
-                    None => (),
-                }
-            }
-            Rule::Ban(ban_rule) => {
-                let new_rule = BanMove::generate_all_variations()
-                    .into_iter()
-                    .filter(|&rule| {
-                        rule != ban_rule && !current_best.clone().strategy.ban_rules.contains(&rule)
-                    })
-                    .choose(&mut rng)
-                    .expect("failed to select new rule");
-                match mutated.strategy.swap_ban_rule(ban_rule, new_rule) {
-                    Some(valid_strategy) => {
-                        mutated.strategy = valid_strategy;
-                        mutated.results = Vec::new();
-                        return mutated;
-                    }
-                    None => (),
-                }
-            }
-        }
-    }
+pub(super) fn permutate_any<S: SearchSpace>(current_best: &StrategyData<S>) -> StrategyData<S> {
+    StrategyData::new(current_best.strategy.mutate())
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::ai::strategy::{attributes::Column, Strategy};
+    use crate::ai::strategy::{attributes::Column, ban_rules::BanMove, Strategy};
     use crate::engine::Move;
 
     #[test]
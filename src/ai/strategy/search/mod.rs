@@ -1,12 +1,17 @@
 use super::evaluate_strategies::StrategyDataStore;
 use super::generate_strategies::permutations;
 use super::mann_whitney::{mann_whitney_u_test, Confidence};
-use super::Strategy;
-use crate::ai::AI;
+use super::parse::load_strategy;
+use super::phased::{load_phased_strategy, PhasedStrategy};
+use super::{Rule, Strategy};
+use crate::ai::noisy::{Mistake, NoiseConfig, Noisy};
+use crate::ai::{play_out, AI};
 use crate::engine::{
     get_highest_tile_val, new_board, Board, GameEngine, GameEngineStores, Move, Score,
 };
+use rand::{seq::IteratorRandom, thread_rng};
 use std::cmp::Ordering;
+use std::fmt;
 use std::fs::File;
 use std::io::Write;
 use std::path::Path;
@@ -18,6 +23,7 @@ pub mod iterated_local;
 pub mod local;
 pub mod progressive_brute_force;
 pub mod random;
+pub mod weighted;
 
 pub fn test_chosen_length(filename: &str) {
     let path = Path::new(filename);
@@ -85,6 +91,44 @@ pub fn search<T: GameEngine>(
     iterated_local::ils_mutate_try_accept_if_better(engine, greedy_results)
 }
 
+// Improve a hand written strategy instead of starting from the greedy search
+pub fn search_from_strategy_file<T: GameEngine>(
+    engine: &T,
+    filename: &str,
+) -> io::Result<StrategyData> {
+    let strategy = load_strategy(filename)?;
+    Ok(iterated_local::ils_mutate_try_accept_if_better(
+        engine,
+        StrategyData::new(strategy),
+    ))
+}
+
+// Like search_from_strategy_file, but mutations can also pick rules written in the condition
+// language
+pub fn search_conditions_from_strategy_file<T: GameEngine>(
+    engine: &T,
+    filename: &str,
+) -> io::Result<StrategyData> {
+    let strategy = load_strategy(filename)?;
+    Ok(iterated_local::ils_mutate_try_conditions_accept_if_better(
+        engine,
+        StrategyData::new(strategy),
+    ))
+}
+
+// Hill climbs a phased strategy, the mutations change the rules and conditions of the phases and
+// add or remove phases
+pub fn search_from_phased_file<T: GameEngine>(
+    engine: &T,
+    filename: &str,
+    iterations: usize,
+) -> io::Result<StrategyData<PhasedStrategy>> {
+    let strategy = load_phased_strategy(filename)?;
+    let mut best = hill_climb(engine, strategy, iterations, 10000);
+    print_best_strategy_info(engine, &mut best);
+    Ok(best)
+}
+
 pub fn test_search_method(
     f: fn(&GameEngineStores, usize, usize) -> StrategyData,
     filename: &str,
@@ -179,6 +223,60 @@ pub fn run_strategy<T: AI, E: GameEngine>(
     }
 }
 
+// Every run starts from the same board
+pub fn run_strategy_from_board<T: AI, E: GameEngine>(
+    ai: &mut T,
+    engine: &E,
+    board: Board,
+    current_results: &mut Vec<Score>,
+    runs: usize,
+) {
+    while current_results.len() < runs {
+        let (final_board, _) = play_out(ai, engine, board, None);
+        current_results.push(engine.get_score(final_board));
+    }
+}
+
+// Plays the strategy with each error rate, the strategy's move being replaced by the mistake that
+// often, and saves the score statistics so the drop in performance can be compared
+pub fn run_strategy_with_errors<E: GameEngine>(
+    engine: &E,
+    strategy: &Strategy,
+    mistake: Mistake,
+    error_rates: &[f64],
+    runs: usize,
+    filename: &str,
+) {
+    assert!(
+        runs > 0,
+        "at least one run is needed for the score statistics"
+    );
+    let mut f = File::create(Path::new(filename)).expect("Failed to create file");
+    f.write_all("error_rate,mistakes,median_score,average_score\n".as_bytes())
+        .expect("Failed to write headers to file");
+    for &error_rate in error_rates {
+        let mut noisy = Noisy::new(strategy.clone(), NoiseConfig::constant(mistake, error_rate));
+        let mut results = Vec::new();
+        run_strategy(&mut noisy, engine, &mut results, runs);
+        let mistake_rate = noisy.get_mistakes() as f64 / noisy.get_moves() as f64;
+        println!(
+            "Error rate: {}\tMistakes: {:.3}\tMedian: {}\tAverage: {}",
+            error_rate,
+            mistake_rate,
+            median(&results),
+            average(&results)
+        );
+        f.write_fmt(format_args!(
+            "{},{},{},{}\n",
+            error_rate,
+            mistake_rate,
+            median(&results),
+            average(&results)
+        ))
+        .expect("Failed to write results to file");
+    }
+}
+
 pub fn run_strategy_save_results(mut ai: Strategy, filename: &str) {
     let engine = GameEngineStores::new();
     let mut f = File::create(Path::new(filename)).expect("Failed to create file");
@@ -226,7 +324,10 @@ fn get_count_mod(len: usize) -> u64 {
     100000
 }
 
-pub fn print_best_strategy_info<T: GameEngine>(engine: &T, strategy_data: &mut StrategyData) {
+pub fn print_best_strategy_info<T: GameEngine, S: AI + fmt::Display>(
+    engine: &T,
+    strategy_data: &mut StrategyData<S>,
+) {
     println!("\n\nGetting stats for best strategy_data...");
     run_strategy(
         &mut strategy_data.strategy,
@@ -293,28 +394,114 @@ fn find_best_fallback_set<T: GameEngine>(engine: &T, strategy: StrategyData) ->
 }
 
 #[derive(Clone)]
-pub struct StrategyData {
-    strategy: Strategy,
+pub struct StrategyData<S = Strategy> {
+    strategy: S,
     results: Vec<Score>,
 }
 
+impl<S> StrategyData<S> {
+    pub fn new(strategy: S) -> Self {
+        StrategyData {
+            strategy,
+            results: vec![],
+        }
+    }
+
+    pub fn get_strategy(&self) -> &S {
+        &self.strategy
+    }
+}
+
+// A kind of strategy the generic searches can explore
+pub trait SearchSpace: AI + Clone + fmt::Display {
+    // A random neighbouring strategy
+    fn mutate(&self) -> Self;
+}
+
+impl SearchSpace for Strategy {
+    // Swaps a random rule for another rule of the same kind
+    fn mutate(&self) -> Self {
+        let mut rng = thread_rng();
+        let rule_to_change = match self.get_rules_ban_first().into_iter().choose(&mut rng) {
+            Some(rule) => rule,
+            None => return self.clone(),
+        };
+        loop {
+            let mutated = match rule_to_change {
+                Rule::Try(try_rule) => {
+                    let new_rule = TryMove::generate_all_variations()
+                        .into_iter()
+                        .filter(|&rule| rule != try_rule && !self.try_rules.contains(&rule))
+                        .choose(&mut rng)
+                        .expect("failed to select new rule");
+                    self.swap_try_rule(try_rule, new_rule)
+                }
+                Rule::Ban(ban_rule) => {
+                    let new_rule = BanMove::generate_all_variations()
+                        .into_iter()
+                        .filter(|&rule| rule != ban_rule && !self.ban_rules.contains(&rule))
+                        .choose(&mut rng)
+                        .expect("failed to select new rule");
+                    self.swap_ban_rule(ban_rule, new_rule)
+                }
+            };
+            if let Some(valid_strategy) = mutated {
+                return valid_strategy;
+            }
+        }
+    }
+}
+
+// Keeps a mutation whenever it beats the current best in a duel, works for any SearchSpace. A
+// duel that is still undecided after max_runs games keeps the current best.
+pub fn hill_climb<S: SearchSpace, T: GameEngine>(
+    engine: &T,
+    strategy: S,
+    iterations: usize,
+    max_runs: usize,
+) -> StrategyData<S> {
+    println!("Starting hill climb...");
+    let mut best = StrategyData::new(strategy);
+    for count in 0..iterations {
+        println!("Mutating {}/{}", count + 1, iterations);
+        let mut mutated = StrategyData::new(best.strategy.mutate());
+        match strategy_duel(
+            engine,
+            &mut best,
+            &mut mutated,
+            Runs {
+                current: 5,
+                max: max_runs,
+            },
+            Confidence::P01,
+        ) {
+            StrategyDuelResult::Champion(results) => best = results,
+            StrategyDuelResult::Challenger(results) => {
+                println!("New best strategy:\n{}", results.strategy);
+                best = results;
+            }
+        }
+    }
+    best
+}
+
 struct Runs {
     current: usize,
     max: usize,
 }
 
-enum StrategyDuelResult {
-    Champion(StrategyData),
-    Challenger(StrategyData),
+enum StrategyDuelResult<S = Strategy> {
+    Champion(StrategyData<S>),
+    Challenger(StrategyData<S>),
 }
 
-fn strategy_duel<T: GameEngine>(
+fn strategy_duel<T: GameEngine, S: AI + Clone>(
     engine: &T,
-    champion: &mut StrategyData,
-    challenger: &mut StrategyData,
+    champion: &mut StrategyData<S>,
+    challenger: &mut StrategyData<S>,
     runs: Runs,
     confidence: Confidence,
-) -> StrategyDuelResult {
+) -> StrategyDuelResult<S> {
     if runs.current > runs.max {
         return StrategyDuelResult::Champion(champion.to_owned());
     }
@@ -346,9 +533,10 @@ fn strategy_duel<T: GameEngine>(
     }
 }
 
-use super::attributes::{is_move_possible, Column, Corner, Row};
+use super::attributes::{Column, Corner, Row};
 use super::ban_rules::BanMove;
 use super::try_rules::TryMove;
+use crate::ai::Explain;
 use crate::engine::GameEngineNoStores;
 use std::io::{self, BufRead};
 
@@ -381,50 +569,18 @@ pub fn add_used_rule_to_data(filename: &str) {
 }
 
 fn find_used_rule(board: Board) -> String {
-    let engine = GameEngineNoStores;
-    let is_up_banned =
-        match BanMove::IfColumnNotLocked(Move::Up, Column::Left).execute(&engine, board) {
-            Some(_) => true,
-            None => false,
-        };
-    match TryMove::IfMovesLargestTileToCorner(Move::Left, Corner::BottomLeft)
-        .execute(&GameEngineNoStores, board)
-    {
-        Some(_) => {
-            return TryMove::IfMovesLargestTileToCorner(Move::Left, Corner::BottomLeft).to_string()
-        }
-        None => (),
-    }
-    if !is_up_banned {
-        match TryMove::ProducesMerge(Move::Up).execute(&engine, board) {
-            Some(_) => return TryMove::ProducesMerge(Move::Up).to_string(),
-            None => (),
-        }
-    }
-    match TryMove::ProducesMerge(Move::Down).execute(&engine, board) {
-        Some(_) => return TryMove::ProducesMerge(Move::Down).to_string(),
-        None => (),
-    }
-    match TryMove::CreatesMonotonicRow(Move::Down, Row::MiddleTop).execute(&engine, board) {
-        Some(_) => return TryMove::CreatesMonotonicRow(Move::Down, Row::MiddleTop).to_string(),
-        None => (),
-    }
-    if is_move_possible(&engine, board, Move::Left) {
-        return "initial fallback left".to_string();
-    }
-    if is_move_possible(&engine, board, Move::Down) {
-        return "initial fallback down".to_string();
-    }
-    if is_move_possible(&engine, board, Move::Up) && !is_up_banned {
-        return "initial fallback up".to_string();
-    }
-    if is_move_possible(&engine, board, Move::Right) {
-        return "initial fallback right".to_string();
-    }
-    if is_move_possible(&engine, board, Move::Up) {
-        return "up forced in fallback".to_string();
-    }
-    return "no possible move".to_string();
+    let mut strategy = Strategy::new(
+        &vec![BanMove::IfColumnNotLocked(Move::Up, Column::Left)],
+        &vec![
+            TryMove::IfMovesLargestTileToCorner(Move::Left, Corner::BottomLeft),
+            TryMove::ProducesMerge(Move::Up),
+            TryMove::ProducesMerge(Move::Down),
+            TryMove::CreatesMonotonicRow(Move::Down, Row::MiddleTop),
+        ],
+        &vec![Move::Left, Move::Down, Move::Up, Move::Right],
+    )
+    .unwrap();
+    strategy.explain(&GameEngineNoStores, board).get_used_rule()
 }
 
 fn read_lines<P>(filename: P) -> io::Result<io::Lines<io::BufReader<File>>>
@@ -434,3 +590,25 @@ where
     let file = File::open(filename)?;
     Ok(io::BufReader::new(file).lines())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::strategy::phased::parse_phased_strategy_file;
+    use crate::engine::GameEngineNoStores;
+
+    #[test]
+    fn it_hill_climb_phased() {
+        let engine = GameEngineNoStores;
+        let phased =
+            parse_phased_strategy_file(include_str!("../../../../strategies/phased_snake.txt"))
+                .unwrap();
+        let best = hill_climb(&engine, phased, 2, 20);
+        // every strategy the climb keeps has been played in a duel and can be written back out
+        assert!(best.results.len() >= 5);
+        assert_eq!(
+            best.get_strategy().to_string().parse::<PhasedStrategy>(),
+            Ok(best.get_strategy().clone())
+        );
+    }
+}
@@ -0,0 +1,75 @@
+use super::iterated_local::{iterated_local_search, permutate_any, ILSVariation};
+use super::{strategy_duel, Runs, StrategyData, StrategyDuelResult};
+use crate::ai::strategy::mann_whitney::Confidence;
+use crate::ai::strategy::weighted::{load_weighted_strategy, WeightedStrategy, MAX_WEIGHT};
+use crate::engine::GameEngine;
+use std::io;
+
+// The local search for weighted strategies, used by the ILS. The rules are changed by the ILS
+// mutations, the local search only tunes the weights: each weight is doubled and halved in turn
+// and a change is kept when it wins the duel, the search restarts after every change.
+
+pub fn local_search_weights<T: GameEngine>(
+    engine: &T,
+    strategy_data: StrategyData<WeightedStrategy>,
+) -> StrategyData<WeightedStrategy> {
+    println!("Starting weight local search...");
+    let mut best_strategy_data = strategy_data;
+    for rule_idx in 0..best_strategy_data.strategy.get_rules().len() {
+        let weighted_rule = best_strategy_data.strategy.get_rules()[rule_idx];
+        println!("Tuning the weight of: {}", weighted_rule);
+        let mut alternative_weights = Vec::new();
+        if weighted_rule.weight < MAX_WEIGHT {
+            alternative_weights.push((weighted_rule.weight * 2).min(MAX_WEIGHT));
+        }
+        if weighted_rule.weight > 1 {
+            alternative_weights.push(weighted_rule.weight / 2);
+        }
+        for alternative_weight in alternative_weights {
+            let mut alternative = best_strategy_data.strategy.clone();
+            alternative.set_weight(rule_idx, alternative_weight);
+            match strategy_duel(
+                engine,
+                &mut best_strategy_data,
+                &mut StrategyData::new(alternative),
+                Runs {
+                    current: 5,
+                    max: 10000,
+                },
+                Confidence::P01,
+            ) {
+                StrategyDuelResult::Champion(results) => best_strategy_data = results,
+                StrategyDuelResult::Challenger(results) => {
+                    println!("Weight changed to {}.", alternative_weight);
+                    println!("Restarting search...");
+                    return local_search_weights(engine, results);
+                }
+            }
+        }
+    }
+    best_strategy_data
+}
+
+pub fn ils_weighted_accept_if_better<T: GameEngine>(
+    engine: &T,
+    strategy_data: StrategyData<WeightedStrategy>,
+) -> StrategyData<WeightedStrategy> {
+    iterated_local_search(
+        engine,
+        strategy_data,
+        local_search_weights,
+        permutate_any,
+        ILSVariation::OnlyAcceptIfBetter,
+    )
+}
+
+pub fn search_from_weighted_file<T: GameEngine>(
+    engine: &T,
+    filename: &str,
+) -> io::Result<StrategyData<WeightedStrategy>> {
+    let strategy = load_weighted_strategy(filename)?;
+    Ok(ils_weighted_accept_if_better(
+        engine,
+        StrategyData::new(strategy),
+    ))
+}
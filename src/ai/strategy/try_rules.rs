@@ -2,6 +2,8 @@ use super::attributes;
 use super::attributes::Column;
 use super::attributes::Corner;
 use super::attributes::Row;
+use super::attributes::SpawnCase;
+use super::condition::{Condition, MAX_TERMS};
 use crate::engine::Board;
 use crate::engine::GameEngine;
 use crate::engine::Move;
@@ -24,6 +26,17 @@ pub enum TryMove {
     RowLocked(Move, Row),
     EmptiesColumn(Move, Column),
     EmptiesRow(Move, Row),
+    ExtendsSnakeChain(Move, Corner),
+    ImprovesGradient(Move, Corner),
+    // looks at the board after the move and the spawn
+    EnablesMergeNextMove(Move, SpawnCase),
+    // thresholds on numeric attributes, tile values are given as values not ranks
+    IfEmptyTilesAtMost(Move, u64),
+    IfUniqueTilesAtLeast(Move, u64),
+    ReducesTilesAtLeast(Move, u64),
+    CreatesSumOrder(Move, Move),
+    // The move is made when the condition holds
+    When(Move, Condition),
 }
 
 impl TryMove {
@@ -62,6 +75,30 @@ impl TryMove {
             TryMove::EmptiesRow(direction, row) => {
                 try_move_if_empties_row(engine, board, *direction, *row)
             }
+            TryMove::ExtendsSnakeChain(direction, corner) => {
+                try_move_if_extends_snake_chain(engine, board, *direction, *corner)
+            }
+            TryMove::ImprovesGradient(direction, corner) => {
+                try_move_if_improves_gradient(engine, board, *direction, *corner)
+            }
+            TryMove::EnablesMergeNextMove(direction, spawn_case) => {
+                try_move_if_enables_merge_next_move(engine, board, *direction, *spawn_case)
+            }
+            TryMove::IfEmptyTilesAtMost(direction, threshold) => {
+                try_move_if_empty_tiles_at_most(board, *direction, *threshold)
+            }
+            TryMove::IfUniqueTilesAtLeast(direction, threshold) => {
+                try_move_if_unique_tiles_at_least(board, *direction, *threshold)
+            }
+            TryMove::ReducesTilesAtLeast(direction, tile_value) => {
+                try_move_if_reduces_tiles_at_least(engine, board, *direction, *tile_value)
+            }
+            TryMove::CreatesSumOrder(direction, towards) => {
+                try_move_if_creates_sum_order(engine, board, *direction, *towards)
+            }
+            TryMove::When(direction, condition) => {
+                try_move_when(engine, board, *direction, *condition)
+            }
         }
     }
 
@@ -80,12 +117,37 @@ impl TryMove {
         variations.append(&mut try_move_if_column_locked_variations());
         variations.append(&mut try_move_if_empties_column_variations());
         variations.append(&mut try_move_if_empties_row_variations());
+        variations.append(&mut try_move_if_extends_snake_chain_variations());
+        variations.append(&mut try_move_if_improves_gradient_variations());
+        variations.append(&mut try_move_if_enables_merge_next_move_variations());
+        variations.append(&mut try_move_if_empty_tiles_at_most_variations());
+        variations.append(&mut try_move_if_unique_tiles_at_least_variations());
+        variations.append(&mut try_move_if_reduces_tiles_at_least_variations());
+        variations.append(&mut try_move_if_creates_sum_order_variations());
         //variations.append(&mut try_move_if_produces_potential_merge_variations_subset());
         //variations.append(&mut try_move_if_merge_possible_variations_subset());
         //variations.append(&mut try_move_if_moves_largest_tile_to_corner_variations_subset());
         variations
     }
 
+    // Rules written in the condition language, these are not part of generate_all_variations as
+    // there are too many for the exhaustive searches
+    pub fn generate_condition_variations(max_terms: usize) -> Vec<Self> {
+        assert!(
+            max_terms <= MAX_TERMS,
+            "a condition has at most {} terms",
+            MAX_TERMS
+        );
+        let conditions = Condition::generate_all_variations(max_terms);
+        let mut variations = Vec::new();
+        for direction in Move::iterator() {
+            for &condition in conditions.iter() {
+                variations.push(TryMove::When(direction, condition));
+            }
+        }
+        variations
+    }
+
     pub fn get_move(&self) -> Move {
         match self {
             TryMove::Always(direction) => *direction,
@@ -101,6 +163,14 @@ impl TryMove {
             TryMove::RowLocked(direction, _) => *direction,
             TryMove::EmptiesColumn(direction, _) => *direction,
             TryMove::EmptiesRow(direction, _) => *direction,
+            TryMove::ExtendsSnakeChain(direction, _) => *direction,
+            TryMove::ImprovesGradient(direction, _) => *direction,
+            TryMove::EnablesMergeNextMove(direction, _) => *direction,
+            TryMove::IfEmptyTilesAtMost(direction, _) => *direction,
+            TryMove::IfUniqueTilesAtLeast(direction, _) => *direction,
+            TryMove::ReducesTilesAtLeast(direction, _) => *direction,
+            TryMove::CreatesSumOrder(direction, _) => *direction,
+            TryMove::When(direction, _) => *direction,
         }
     }
 }
@@ -151,6 +221,44 @@ impl fmt::Display for TryMove {
             TryMove::EmptiesRow(direction, row) => {
                 write!(f, "try move {} if empties {} row", direction, row)
             }
+            TryMove::ExtendsSnakeChain(direction, corner) => write!(
+                f,
+                "try move {} if extends snake chain from {} corner",
+                direction, corner
+            ),
+            TryMove::ImprovesGradient(direction, corner) => write!(
+                f,
+                "try move {} if improves gradient towards {} corner",
+                direction, corner
+            ),
+            TryMove::EnablesMergeNextMove(direction, spawn_case) => write!(
+                f,
+                "try move {} if enables merge next move in {}",
+                direction, spawn_case
+            ),
+            TryMove::IfEmptyTilesAtMost(direction, threshold) => write!(
+                f,
+                "try move {} if at most {} empty tiles",
+                direction, threshold
+            ),
+            TryMove::IfUniqueTilesAtLeast(direction, threshold) => write!(
+                f,
+                "try move {} if at least {} unique tiles",
+                direction, threshold
+            ),
+            TryMove::ReducesTilesAtLeast(direction, tile_value) => write!(
+                f,
+                "try move {} if reduces tiles of at least {}",
+                direction, tile_value
+            ),
+            TryMove::CreatesSumOrder(direction, towards) => write!(
+                f,
+                "try move {} if creates sum order towards {}",
+                direction, towards
+            ),
+            TryMove::When(direction, condition) => {
+                write!(f, "try move {} when {}", direction, condition)
+            }
         }
     }
 }
@@ -333,6 +441,114 @@ fn try_move_if_empties_row<T: GameEngine>(
     None
 }
 
+fn try_move_if_extends_snake_chain<T: GameEngine>(
+    engine: &T,
+    board: Board,
+    direction: Move,
+    corner: Corner,
+) -> Option<Move> {
+    let length = attributes::snake_chain_length(board, corner);
+    let new_board = engine.shift(board, direction);
+    let new_length = attributes::snake_chain_length(new_board, corner);
+    if new_length > length {
+        return Some(direction);
+    }
+    None
+}
+
+fn try_move_if_improves_gradient<T: GameEngine>(
+    engine: &T,
+    board: Board,
+    direction: Move,
+    corner: Corner,
+) -> Option<Move> {
+    let conformity = attributes::gradient_conformity(board, corner);
+    let new_board = engine.shift(board, direction);
+    let new_conformity = attributes::gradient_conformity(new_board, corner);
+    if new_conformity > conformity {
+        return Some(direction);
+    }
+    None
+}
+
+fn try_move_if_enables_merge_next_move<T: GameEngine>(
+    engine: &T,
+    board: Board,
+    direction: Move,
+    spawn_case: SpawnCase,
+) -> Option<Move> {
+    if attributes::holds_after_spawn(
+        engine,
+        board,
+        direction,
+        spawn_case,
+        attributes::is_any_merge_possible,
+    ) {
+        return Some(direction);
+    }
+    None
+}
+
+fn try_move_if_empty_tiles_at_most(board: Board, direction: Move, threshold: u64) -> Option<Move> {
+    if attributes::count_empty_tiles(board) <= threshold {
+        return Some(direction);
+    }
+    None
+}
+
+fn try_move_if_unique_tiles_at_least(
+    board: Board,
+    direction: Move,
+    threshold: u64,
+) -> Option<Move> {
+    if attributes::count_unique_tiles(board) >= threshold {
+        return Some(direction);
+    }
+    None
+}
+
+fn try_move_if_reduces_tiles_at_least<T: GameEngine>(
+    engine: &T,
+    board: Board,
+    direction: Move,
+    tile_value: u64,
+) -> Option<Move> {
+    let count = attributes::count_tiles_at_least(board, tile_value);
+    let new_board = engine.shift(board, direction);
+    let new_count = attributes::count_tiles_at_least(new_board, tile_value);
+    if new_count < count {
+        return Some(direction);
+    }
+    None
+}
+
+fn try_move_if_creates_sum_order<T: GameEngine>(
+    engine: &T,
+    board: Board,
+    direction: Move,
+    towards: Move,
+) -> Option<Move> {
+    let is_ordered = attributes::is_sum_ordered(board, towards);
+    let new_board = engine.shift(board, direction);
+    let is_new_ordered = attributes::is_sum_ordered(new_board, towards);
+    if !is_ordered && is_new_ordered {
+        return Some(direction);
+    }
+    None
+}
+
+fn try_move_when<T: GameEngine>(
+    engine: &T,
+    board: Board,
+    direction: Move,
+    condition: Condition,
+) -> Option<Move> {
+    if condition.holds(engine, board, direction) {
+        return Some(direction);
+    }
+    None
+}
+
 fn try_move_if_produces_potential_merge_variations() -> Vec<TryMove> {
     Move::iterator().fold(Vec::new(), |mut variations, direction| {
         variations.push(TryMove::ProducesMerge(direction));
@@ -525,6 +741,76 @@ fn try_move_if_empties_row_variations() -> Vec<TryMove> {
     variations
 }
 
+fn try_move_if_extends_snake_chain_variations() -> Vec<TryMove> {
+    let mut variations = Vec::new();
+    for direction in Move::iterator() {
+        for corner in Corner::iterator() {
+            variations.push(TryMove::ExtendsSnakeChain(direction, corner));
+        }
+    }
+    variations
+}
+
+fn try_move_if_improves_gradient_variations() -> Vec<TryMove> {
+    let mut variations = Vec::new();
+    for direction in Move::iterator() {
+        for corner in Corner::iterator() {
+            variations.push(TryMove::ImprovesGradient(direction, corner));
+        }
+    }
+    variations
+}
+
+fn try_move_if_enables_merge_next_move_variations() -> Vec<TryMove> {
+    let mut variations = Vec::new();
+    for direction in Move::iterator() {
+        for spawn_case in SpawnCase::iterator() {
+            variations.push(TryMove::EnablesMergeNextMove(direction, spawn_case));
+        }
+    }
+    variations
+}
+
+fn try_move_if_empty_tiles_at_most_variations() -> Vec<TryMove> {
+    let mut variations = Vec::new();
+    for direction in Move::iterator() {
+        for &threshold in [1, 2, 4].iter() {
+            variations.push(TryMove::IfEmptyTilesAtMost(direction, threshold));
+        }
+    }
+    variations
+}
+
+fn try_move_if_unique_tiles_at_least_variations() -> Vec<TryMove> {
+    let mut variations = Vec::new();
+    for direction in Move::iterator() {
+        for &threshold in [6, 8, 10].iter() {
+            variations.push(TryMove::IfUniqueTilesAtLeast(direction, threshold));
+        }
+    }
+    variations
+}
+
+fn try_move_if_reduces_tiles_at_least_variations() -> Vec<TryMove> {
+    let mut variations = Vec::new();
+    for direction in Move::iterator() {
+        for &tile_value in [64, 128, 256, 512].iter() {
+            variations.push(TryMove::ReducesTilesAtLeast(direction, tile_value));
+        }
+    }
+    variations
+}
+
+fn try_move_if_creates_sum_order_variations() -> Vec<TryMove> {
+    let mut variations = Vec::new();
+    for direction in Move::iterator() {
+        for towards in Move::iterator() {
+            variations.push(TryMove::CreatesSumOrder(direction, towards));
+        }
+    }
+    variations
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -543,4 +829,84 @@ mod tests {
             None
         );
     }
+
+    #[test]
+    fn it_threshold_rules() {
+        let engine = GameEngineNoStores;
+        let crowded = 0x1234432112344300;
+        assert_eq!(
+            TryMove::IfEmptyTilesAtMost(Move::Right, 2).execute(&engine, crowded),
+            Some(Move::Right)
+        );
+        assert_eq!(
+            TryMove::IfEmptyTilesAtMost(Move::Right, 1).execute(&engine, crowded),
+            None
+        );
+        assert_eq!(
+            TryMove::IfUniqueTilesAtLeast(Move::Right, 5).execute(&engine, crowded),
+            None
+        );
+        // merging the two 8s leaves one tile of at least 8
+        let board = 0x0000000000000033;
+        assert_eq!(
+            TryMove::ReducesTilesAtLeast(Move::Left, 8).execute(&engine, board),
+            Some(Move::Left)
+        );
+        assert_eq!(
+            TryMove::ReducesTilesAtLeast(Move::Up, 8).execute(&engine, board),
+            None
+        );
+    }
+
+    #[test]
+    fn it_snake_chain_rules() {
+        use super::super::ban_rules::BanMove;
+        let engine = GameEngineNoStores;
+        // moving down puts the 4 next to the 5 in the chain from the bottom left corner
+        let board = 0x0000000000047650;
+        assert_eq!(
+            TryMove::ExtendsSnakeChain(Move::Down, Corner::BottomLeft).execute(&engine, board),
+            Some(Move::Down)
+        );
+        assert_eq!(
+            TryMove::ExtendsSnakeChain(Move::Left, Corner::BottomLeft).execute(&engine, board),
+            None
+        );
+        assert_eq!(
+            BanMove::BreaksSnakeChain(Move::Right, Corner::BottomLeft).execute(&engine, board),
+            Some(Move::Right)
+        );
+        assert_eq!(
+            BanMove::BreaksSnakeChain(Move::Left, Corner::BottomLeft).execute(&engine, board),
+            None
+        );
+    }
+
+    #[test]
+    fn it_lookahead_rules() {
+        use super::super::ban_rules::BanMove;
+        let engine = GameEngineNoStores;
+        // after moving left a 2 can spawn next to the 2 but it does not have to
+        let board = 0x0000000000000001;
+        assert_eq!(
+            TryMove::EnablesMergeNextMove(Move::Left, SpawnCase::Best).execute(&engine, board),
+            Some(Move::Left)
+        );
+        assert_eq!(
+            TryMove::EnablesMergeNextMove(Move::Left, SpawnCase::Worst).execute(&engine, board),
+            None
+        );
+        // the 4 in the corner moves up and nothing can bring it back if the spawn is below it
+        let board = 0x0000000000000002;
+        assert_eq!(
+            BanMove::EveryNextMoveLosesCorner(Move::Up, Corner::BottomRight, SpawnCase::Worst)
+                .execute(&engine, board),
+            Some(Move::Up)
+        );
+        assert_eq!(
+            BanMove::EveryNextMoveLosesCorner(Move::Up, Corner::BottomRight, SpawnCase::Best)
+                .execute(&engine, board),
+            None
+        );
+    }
 }
@@ -0,0 +1,394 @@
+use super::attributes;
+use super::ban_rules::BanMove;
+use super::fallback::BannedPolicy;
+use super::parse::ParseError;
+use super::search::SearchSpace;
+use super::try_rules::TryMove;
+use super::{Rule, Strategy};
+use crate::ai::{MoveScores, ScoreMoves, AI};
+use crate::engine::{Board, GameEngine, Move};
+use rand::{seq::IteratorRandom, thread_rng, Rng};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::str::FromStr;
+
+// A strategy where every rule votes instead of the first try rule winning. A try rule that fires
+// adds its weight to its move and a ban rule that fires takes its weight away, the possible move
+// with the highest total is played and ties go to the earliest move in the tie break order.
+//
+// Written one rule per line with the weight first, try rules vote + and ban rules vote -:
+//
+//   # keep the largest tile in the corner
+//   -16 ban move up if left column not locked
+//   +8 try move left if moves largest tile to bottom left corner
+//   +4 try move up if produces merge
+//   ties left->up->down->right
+//
+// Blank lines and lines starting with # are ignored. Weights go from 1 to MAX_WEIGHT, which keeps
+// the vote totals of even thousands of rules far from overflowing.
+
+pub const MAX_WEIGHT: u64 = 1 << 32;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct WeightedRule {
+    pub rule: Rule,
+    pub weight: u64,
+}
+
+impl WeightedRule {
+    // The move the rule votes on and the signed vote, None if the rule does not fire
+    pub fn vote<T: GameEngine>(&self, engine: &T, board: Board) -> Option<(Move, i64)> {
+        match self.rule {
+            Rule::Try(try_rule) => try_rule
+                .execute(engine, board)
+                .map(|direction| (direction, self.signed_weight())),
+            Rule::Ban(ban_rule) => ban_rule
+                .execute(engine, board)
+                .map(|direction| (direction, -self.signed_weight())),
+        }
+    }
+
+    // A weight set above MAX_WEIGHT votes as MAX_WEIGHT
+    fn signed_weight(&self) -> i64 {
+        self.weight.min(MAX_WEIGHT) as i64
+    }
+}
+
+impl fmt::Display for WeightedRule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.rule {
+            Rule::Try(try_rule) => write!(f, "+{} {}", self.weight, try_rule),
+            Rule::Ban(ban_rule) => write!(f, "-{} {}", self.weight, ban_rule),
+        }
+    }
+}
+
+impl FromStr for WeightedRule {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let unknown_rule = || ParseError::UnknownValue("weighted rule", s.to_string());
+        let (weight, rule) = s.split_once(' ').ok_or_else(unknown_rule)?;
+        // the sign gives the kind of rule
+        let rule = match weight.chars().next() {
+            Some('+') => Rule::Try(rule.parse::<TryMove>()?),
+            Some('-') => Rule::Ban(rule.parse::<BanMove>()?),
+            _ => return Err(unknown_rule()),
+        };
+        let weight = match weight[1..].parse::<u64>() {
+            Ok(value) if (1..=MAX_WEIGHT).contains(&value) => value,
+            Ok(_) => return Err(ParseError::OutOfRange("weight", weight.to_string())),
+            Err(_) => return Err(ParseError::UnknownValue("weight", weight.to_string())),
+        };
+        Ok(WeightedRule { rule, weight })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct WeightedStrategy {
+    rules: Vec<WeightedRule>,
+    tie_break: Vec<Move>,
+}
+
+impl WeightedStrategy {
+    // None if a ban rule has a parameter out of range, like Strategy::new
+    pub fn new(rules: Vec<WeightedRule>, tie_break: Vec<Move>) -> Option<Self> {
+        let valid = rules.iter().all(|weighted_rule| match weighted_rule.rule {
+            Rule::Ban(ban_rule) => ban_rule.is_valid(),
+            Rule::Try(_) => true,
+        });
+        if !valid {
+            return None;
+        }
+        Some(WeightedStrategy { rules, tie_break })
+    }
+
+    // Weights for the rules of an ordered strategy: each try rule outweighs all of the try rules
+    // after it and every ban outweighs all of the try rules, the fallback moves break ties. This
+    // plays the same move as the ordered strategy whenever a possible move is not banned. When
+    // every possible move is banned the ordered strategy forces the first legal fallback move but
+    // the weighted one forces the move with the highest total, so they can differ.
+    //
+    // None if the strategy has fallback preferences or a banned policy other than first legal,
+    // votes cannot express them, or if it has too many try rules for the weights to order
+    pub fn from_strategy(strategy: &Strategy) -> Option<Self> {
+        if !strategy.fallback_preferences.is_empty()
+            || strategy.banned_policy != BannedPolicy::FirstLegal
+        {
+            return None;
+        }
+        let num_try_rules = strategy.try_rules.len() as u32;
+        if 2_u64.checked_pow(num_try_rules)? > MAX_WEIGHT {
+            return None;
+        }
+        let mut rules = strategy
+            .ban_rules
+            .iter()
+            .map(|&ban_rule| WeightedRule {
+                rule: Rule::Ban(ban_rule),
+                weight: 2_u64.pow(num_try_rules),
+            })
+            .collect::<Vec<_>>();
+        for (idx, &try_rule) in strategy.try_rules.iter().enumerate() {
+            rules.push(WeightedRule {
+                rule: Rule::Try(try_rule),
+                weight: 2_u64.pow(num_try_rules - 1 - idx as u32),
+            });
+        }
+        WeightedStrategy::new(rules, strategy.fallback_moves.clone())
+    }
+
+    pub fn get_rules(&self) -> &Vec<WeightedRule> {
+        &self.rules
+    }
+
+    pub fn get_tie_break(&self) -> &Vec<Move> {
+        &self.tie_break
+    }
+
+    // The weight is clamped to 1..=MAX_WEIGHT
+    pub fn set_weight(&mut self, rule_idx: usize, weight: u64) {
+        self.rules[rule_idx].weight = weight.clamp(1, MAX_WEIGHT);
+    }
+
+    // The vote total of every move, moves not in the tie break order are checked after it
+    pub fn get_votes<T: GameEngine>(&self, engine: &T, board: Board) -> Vec<(Move, i64)> {
+        let mut votes = self
+            .tie_break
+            .iter()
+            .cloned()
+            .chain(Move::iterator().filter(|direction| !self.tie_break.contains(direction)))
+            .map(|direction| (direction, 0_i64))
+            .collect::<Vec<_>>();
+        for weighted_rule in &self.rules {
+            if let Some((direction, vote)) = weighted_rule.vote(engine, board) {
+                for (other, total) in votes.iter_mut() {
+                    if *other == direction {
+                        *total = total.saturating_add(vote);
+                    }
+                }
+            }
+        }
+        votes
+    }
+
+    // The possible moves from the highest vote to the lowest, ties keep the tie break order
+    fn rank_moves<T: GameEngine>(&self, engine: &T, board: Board) -> Vec<Move> {
+        let mut votes = self
+            .get_votes(engine, board)
+            .into_iter()
+            .filter(|&(direction, _)| attributes::is_move_possible(engine, board, direction))
+            .collect::<Vec<_>>();
+        votes.sort_by(|(_, a), (_, b)| b.cmp(a));
+        votes.into_iter().map(|(direction, _)| direction).collect()
+    }
+}
+
+impl AI for WeightedStrategy {
+    fn get_next_move<T: GameEngine>(&mut self, engine: &T, board: Board) -> Option<Move> {
+        self.rank_moves(engine, board).first().cloned()
+    }
+}
+
+impl ScoreMoves for WeightedStrategy {
+    // Ranked like Strategy, the preferred move scores the number of moves ranked
+    fn score_moves<T: GameEngine>(&mut self, engine: &T, board: Board) -> MoveScores {
+        let ranked = self.rank_moves(engine, board);
+        let mut move_scores = MoveScores::new();
+        for (idx, &direction) in ranked.iter().enumerate() {
+            move_scores.insert(direction, (ranked.len() - idx) as f64);
+        }
+        move_scores
+    }
+}
+
+impl SearchSpace for WeightedStrategy {
+    // Doubles or halves the weight of a random rule, or swaps the rule for another of the same
+    // kind keeping its weight
+    fn mutate(&self) -> Self {
+        let mut rng = thread_rng();
+        let mut mutated = self.clone();
+        let rule_idx = match (0..self.rules.len()).choose(&mut rng) {
+            Some(rule_idx) => rule_idx,
+            None => return mutated,
+        };
+        let weighted_rule = &mut mutated.rules[rule_idx];
+        match rng.gen_range(0, 3) {
+            0 if weighted_rule.weight < MAX_WEIGHT => {
+                weighted_rule.weight = (weighted_rule.weight * 2).min(MAX_WEIGHT)
+            }
+            1 if weighted_rule.weight > 1 => weighted_rule.weight /= 2,
+            _ => {
+                let current_rules = self
+                    .rules
+                    .iter()
+                    .map(|weighted_rule| weighted_rule.rule)
+                    .collect::<Vec<_>>();
+                weighted_rule.rule = match weighted_rule.rule {
+                    Rule::Try(_) => TryMove::generate_all_variations()
+                        .into_iter()
+                        .map(Rule::Try)
+                        .filter(|rule| !current_rules.contains(rule))
+                        .choose(&mut rng),
+                    Rule::Ban(_) => BanMove::generate_all_variations()
+                        .into_iter()
+                        .map(Rule::Ban)
+                        .filter(|rule| !current_rules.contains(rule))
+                        .choose(&mut rng),
+                }
+                .expect("failed to select new rule");
+            }
+        }
+        mutated
+    }
+}
+
+impl fmt::Display for WeightedStrategy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for weighted_rule in &self.rules {
+            writeln!(f, "{}", weighted_rule)?;
+        }
+        let tie_break = self
+            .tie_break
+            .iter()
+            .map(|direction| direction.to_string())
+            .collect::<Vec<_>>();
+        writeln!(f, "ties {}", tie_break.join("->"))
+    }
+}
+
+impl FromStr for WeightedStrategy {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut rules = Vec::new();
+        let mut tie_break = None;
+        for (line_idx, line) in s.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let invalid_line =
+                |error| ParseError::InvalidItem("line", line_idx + 1, Box::new(error));
+            if let Some(moves) = line.strip_prefix("ties ") {
+                tie_break = Some(
+                    moves
+                        .split("->")
+                        .map(|direction| direction.parse::<Move>())
+                        .collect::<Result<Vec<_>, _>>()
+                        .map_err(invalid_line)?,
+                );
+            } else {
+                rules.push(line.parse::<WeightedRule>().map_err(invalid_line)?);
+            }
+        }
+        let tie_break = tie_break.ok_or(ParseError::MissingSection("ties"))?;
+        WeightedStrategy::new(rules, tie_break)
+            .ok_or_else(|| ParseError::InvalidStrategy(s.trim().to_string()))
+    }
+}
+
+pub fn load_weighted_strategy(filename: &str) -> io::Result<WeightedStrategy> {
+    fs::read_to_string(filename)?
+        .parse::<WeightedStrategy>()
+        .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::attributes::Column;
+    use super::super::fallback::HarmScore;
+    use super::*;
+    use crate::engine::GameEngineNoStores;
+
+    #[test]
+    fn it_weighted_strategy() {
+        let engine = GameEngineNoStores;
+        let snake = super::super::snake();
+        let mut weighted = WeightedStrategy::from_strategy(&snake).unwrap();
+        assert_eq!(
+            WeightedStrategy::from_strategy(
+                &snake
+                    .clone()
+                    .with_banned_policy(BannedPolicy::LeastHarmful(HarmScore::EmptyTiles))
+            ),
+            None
+        );
+        assert_eq!(
+            include_str!("../../../strategies/weighted_snake.txt").parse::<WeightedStrategy>(),
+            Ok(weighted.clone())
+        );
+        assert_eq!(
+            weighted.to_string().parse::<WeightedStrategy>(),
+            Ok(weighted.clone())
+        );
+        assert_eq!(
+            weighted.get_rules()[0].to_string(),
+            "-16 ban move up if left column not locked"
+        );
+
+        // no try rule fires and up is banned, so the tie break picks left like the fallback does
+        let board = 0x0000010001000000;
+        let mut ordered = snake.clone();
+        assert_eq!(weighted.get_next_move(&engine, board), Some(Move::Left));
+        assert_eq!(
+            weighted.get_next_move(&engine, board),
+            ordered.get_next_move(&engine, board)
+        );
+        assert_eq!(
+            weighted.score_moves(&engine, board).best(),
+            Some(Move::Left)
+        );
+
+        // enough votes for up outweigh the ban
+        let mut votes = WeightedStrategy::new(
+            vec![
+                WeightedRule {
+                    rule: Rule::Ban(BanMove::IfColumnNotLocked(Move::Up, Column::Left)),
+                    weight: 2,
+                },
+                WeightedRule {
+                    rule: Rule::Try(TryMove::Always(Move::Up)),
+                    weight: 3,
+                },
+            ],
+            vec![Move::Left, Move::Down],
+        )
+        .unwrap();
+        assert_eq!(votes.get_next_move(&engine, board), Some(Move::Up));
+        assert_eq!(
+            WeightedStrategy::new(
+                vec![WeightedRule {
+                    rule: Rule::Ban(BanMove::IfGameOverChanceAbove(Move::Up, 150)),
+                    weight: 1,
+                }],
+                vec![Move::Left],
+            ),
+            None
+        );
+        for _ in 0..100 {
+            let mutated = votes.mutate();
+            assert_eq!(mutated.to_string().parse::<WeightedStrategy>(), Ok(mutated));
+        }
+        assert!("+2 ban move up if left column not locked\nties left"
+            .parse::<WeightedStrategy>()
+            .is_err());
+        assert_eq!(
+            "+0 try move up if produces merge".parse::<WeightedRule>(),
+            Err(ParseError::OutOfRange("weight", "+0".to_string()))
+        );
+        assert_eq!(
+            format!("-{} ban move up if left column not locked", MAX_WEIGHT + 1)
+                .parse::<WeightedRule>(),
+            Err(ParseError::OutOfRange(
+                "weight",
+                format!("-{}", MAX_WEIGHT + 1)
+            ))
+        );
+        votes.set_weight(1, u64::MAX);
+        assert_eq!(votes.get_rules()[1].weight, MAX_WEIGHT);
+        assert_eq!(votes.get_next_move(&engine, board), Some(Move::Up));
+    }
+}
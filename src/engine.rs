@@ -35,7 +35,8 @@ type Line = u64;
 type Tile = u64;
 pub type Score = u64;
 
-pub trait GameEngine {
+// Engines are shared between threads by the multithreaded searches
+pub trait GameEngine: Sync {
     fn get_score(&self, board: Board) -> Score;
 
     fn shift_rows(&self, board: Board, move_dir: Move) -> Board;
@@ -50,6 +51,14 @@ pub trait GameEngine {
         board
     }
 
+    fn make_move_with_rng<R: Rng>(&self, board: Board, direction: Move, rng: &mut R) -> Board {
+        let new_board = self.shift(board, direction);
+        if board != new_board {
+            return insert_random_tile_with_rng(new_board, rng);
+        }
+        board
+    }
+
     fn shift(&self, board: Board, direction: Move) -> Board {
         match direction {
             Move::Left | Move::Right => self.shift_rows(board, direction),
@@ -180,6 +189,11 @@ pub fn new_board() -> Board {
     insert_random_tile(board)
 }
 
+pub fn new_board_with_rng<R: Rng>(rng: &mut R) -> Board {
+    let board = insert_random_tile_with_rng(0, rng);
+    insert_random_tile_with_rng(board, rng)
+}
+
 // Credit to Nneonneo
 pub fn transpose(x: Board) -> Board {
     let a1 = x & 0xF0F00F0FF0F00F0F;
@@ -225,6 +239,26 @@ pub fn count_empty(board: Board) -> u64 {
     16 - count_non_empty(board)
 }
 
+// Indexes of the empty tiles, top left first
+pub fn get_empty_idxs(board: Board) -> impl Iterator<Item = usize> {
+    (0..16).filter(move |&idx| get_tile(board, idx) == 0)
+}
+
+// Every board the spawn after a move can make with its probability, the spawned tile is a 2 nine
+// times in ten
+pub fn get_spawns(board: Board) -> Vec<(Board, f64)> {
+    let num_empty = count_empty(board) as f64;
+    get_empty_idxs(board)
+        .flat_map(|idx| {
+            let shift = 60 - 4 * idx;
+            vec![
+                (board | (1 << shift), 0.9 / num_empty),
+                (board | (2 << shift), 0.1 / num_empty),
+            ]
+        })
+        .collect()
+}
+
 pub fn to_str(board: Board) -> String {
     let board: Vec<_> = to_vec(board).iter().map(|x| format_val(x)).collect();
     format!(
@@ -256,12 +290,15 @@ pub fn to_str(board: Board) -> String {
     )
 }
 
-// Credit to Nneonneo
 fn insert_random_tile(board: Board) -> Board {
-    let mut rng = rand::thread_rng();
+    insert_random_tile_with_rng(board, &mut rand::thread_rng())
+}
+
+// Credit to Nneonneo
+pub fn insert_random_tile_with_rng<R: Rng>(board: Board, rng: &mut R) -> Board {
     let mut index = rng.gen_range(0, count_empty(board));
     let mut tmp = board;
-    let mut tile = generate_random_tile();
+    let mut tile = generate_random_tile(rng);
     loop {
         while (tmp & 0xf) != 0 {
             tmp >>= 4;
@@ -277,8 +314,7 @@ fn insert_random_tile(board: Board) -> Board {
     return board | tile;
 }
 
-fn generate_random_tile() -> Tile {
-    let mut rng = rand::thread_rng();
+fn generate_random_tile<R: Rng>(rng: &mut R) -> Tile {
     if rng.gen_range(0, 10) < 9 {
         1
     } else {
@@ -458,6 +494,15 @@ mod tests {
         assert_eq!(count_empty(game), 0);
     }
 
+    #[test]
+    fn it_new_board_with_rng() {
+        use rand::{rngs::StdRng, SeedableRng};
+        let board1 = new_board_with_rng(&mut StdRng::seed_from_u64(7));
+        let board2 = new_board_with_rng(&mut StdRng::seed_from_u64(7));
+        assert_eq!(board1, board2);
+        assert_eq!(count_empty(board1), 14);
+    }
+
     #[test]
     fn test_unoptimised_shift() {
         let state1 = vec![
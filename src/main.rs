@@ -1,11 +1,20 @@
 #![allow(unused_imports)]
+use msc_2048_ai::ai::adversarial::{run_adversarial, MinimaxSpawner, RandomSpawner};
+use msc_2048_ai::ai::benchmark::{generate_positions, load_positions, save_positions, score_agent};
+use msc_2048_ai::ai::expectimax::tune::{tune_weights, TuneConfig};
+use msc_2048_ai::ai::expectimax::{DepthPolicy, Expectimax, ExpectimaxConfig};
+use msc_2048_ai::ai::ntuple;
+use msc_2048_ai::ai::ntuple::{NTupleNetwork, TrainConfig};
 use msc_2048_ai::ai::run_ai_with_delay;
-use msc_2048_ai::ai::strategy::search::search;
-use msc_2048_ai::ai::strategy::{
-    attributes::Column, attributes::Corner, attributes::Row, ban_rules::BanMove,
-    try_rules::TryMove, Strategy,
+use msc_2048_ai::ai::scenario::{load_scenarios, run_scenario_suite};
+use msc_2048_ai::ai::strategy;
+use msc_2048_ai::ai::strategy::parse::{load_strategy, to_strategy_file};
+use msc_2048_ai::ai::strategy::search::weighted::search_from_weighted_file;
+use msc_2048_ai::ai::strategy::search::{
+    median, search, search_conditions_from_strategy_file, search_from_phased_file,
+    search_from_strategy_file,
 };
-use msc_2048_ai::engine::{GameEngineNoStores, GameEngineStores, Move};
+use msc_2048_ai::engine::{GameEngineNoStores, GameEngineStores};
 use std::env;
 
 fn main() {
@@ -15,17 +24,22 @@ fn main() {
             let cmd = &args[1];
             match &cmd[..] {
                 "--play" => {
-                    let ban_rules = vec![BanMove::IfColumnNotLocked(Move::Up, Column::Left)];
-                    let try_rules = vec![
-                        TryMove::IfMovesLargestTileToCorner(Move::Left, Corner::BottomLeft),
-                        TryMove::ProducesMerge(Move::Up),
-                        TryMove::ProducesMerge(Move::Down),
-                        TryMove::CreatesMonotonicRow(Move::Down, Row::MiddleTop),
-                    ];
-                    let fallback = vec![Move::Left, Move::Up, Move::Down, Move::Right];
-                    let mut strategy = Strategy::new(&ban_rules, &try_rules, &fallback).unwrap();
+                    let mut strategy = strategy::snake();
                     run_ai_with_delay(&mut strategy, 500);
                 }
+                "--adversarial" => {
+                    let mut strategy = strategy::snake();
+                    let engine = GameEngineStores::new();
+                    let random_scores =
+                        run_adversarial(&mut strategy, &mut RandomSpawner::new(), &engine, 100);
+                    let worst_case_scores =
+                        run_adversarial(&mut strategy, &mut MinimaxSpawner::new(2), &engine, 100);
+                    println!(
+                        "Median score with random spawns: {}\nMedian score with worst case spawns: {}",
+                        median(&random_scores),
+                        median(&worst_case_scores)
+                    );
+                }
                 _ => {
                     let engine = GameEngineStores::new();
                     search(&engine, 1, 4);
@@ -38,28 +52,102 @@ fn main() {
             match &cmd[..] {
                 "--play" => match &move_rate[..].parse::<u64>() {
                     Ok(move_rate_int) => {
-                        let ban_rules = vec![BanMove::IfColumnNotLocked(Move::Up, Column::Left)];
-                        let try_rules = vec![
-                            TryMove::IfMovesLargestTileToCorner(Move::Left, Corner::BottomLeft),
-                            TryMove::ProducesMerge(Move::Up),
-                            TryMove::ProducesMerge(Move::Down),
-                            TryMove::CreatesMonotonicRow(Move::Down, Row::MiddleTop),
-                        ];
-                        let fallback = vec![Move::Left, Move::Up, Move::Down, Move::Right];
-                        let mut strategy =
-                            Strategy::new(&ban_rules, &try_rules, &fallback).unwrap();
+                        let mut strategy = strategy::snake();
                         run_ai_with_delay(&mut strategy, *move_rate_int);
                     }
-                    Err(err) => {
-                        eprintln!("Invalid time: {}", err);
-                    }
+                    // not a move rate so it must be a strategy file
+                    Err(_) => match load_strategy(&args[2]) {
+                        Ok(mut strategy) => run_ai_with_delay(&mut strategy, 500),
+                        Err(err) => eprintln!("Invalid strategy file: {}", err),
+                    },
                 },
+                "--search" => {
+                    let engine = GameEngineStores::new();
+                    match search_from_strategy_file(&engine, &args[2]) {
+                        Ok(strategy_data) => {
+                            print!("{}", to_strategy_file(strategy_data.get_strategy()))
+                        }
+                        Err(err) => eprintln!("Invalid strategy file: {}", err),
+                    }
+                }
+                "--search-conditions" => {
+                    let engine = GameEngineStores::new();
+                    match search_conditions_from_strategy_file(&engine, &args[2]) {
+                        Ok(strategy_data) => {
+                            print!("{}", to_strategy_file(strategy_data.get_strategy()))
+                        }
+                        Err(err) => eprintln!("Invalid strategy file: {}", err),
+                    }
+                }
+                "--search-weighted" => {
+                    let engine = GameEngineStores::new();
+                    match search_from_weighted_file(&engine, &args[2]) {
+                        Ok(strategy_data) => print!("{}", strategy_data.get_strategy()),
+                        Err(err) => eprintln!("Invalid weighted strategy file: {}", err),
+                    }
+                }
+                "--search-phased" => {
+                    let engine = GameEngineStores::new();
+                    match search_from_phased_file(&engine, &args[2], 100) {
+                        Ok(strategy_data) => println!("{}", strategy_data.get_strategy()),
+                        Err(err) => eprintln!("Invalid phased strategy file: {}", err),
+                    }
+                }
+                "--generate-benchmark" => {
+                    let engine = GameEngineStores::new();
+                    let mut player = Expectimax::with_config(ExpectimaxConfig {
+                        depth_policy: DepthPolicy::Fixed(2),
+                        ..ExpectimaxConfig::default()
+                    });
+                    let mut labeller = Expectimax::new();
+                    let positions =
+                        generate_positions(&mut player, &mut labeller, &engine, 5000, 10);
+                    save_positions(&positions, &args[2])
+                        .expect("Failed to save benchmark positions");
+                }
+                "--benchmark" => {
+                    let mut strategy = strategy::snake();
+                    let positions =
+                        load_positions(&args[2]).expect("Failed to load benchmark positions");
+                    let engine = GameEngineStores::new();
+                    match score_agent(&mut strategy, &engine, &positions) {
+                        Some(score) => println!("{}", score),
+                        None => eprintln!("No benchmark positions in {}", args[2]),
+                    }
+                }
+                "--scenarios" => {
+                    let mut strategy = strategy::snake();
+                    let scenarios = load_scenarios(&args[2]).expect("Failed to load scenarios");
+                    let engine = GameEngineStores::new();
+                    run_scenario_suite(&mut strategy, &engine, &scenarios, 1000, 100);
+                }
+                "--tune-expectimax" => {
+                    let engine = GameEngineStores::new();
+                    match tune_weights(&engine, TuneConfig::default(), &args[2]) {
+                        Ok(weights) => println!("{:?}", weights),
+                        Err(err) => eprintln!("Failed to save tuned weights: {}", err),
+                    }
+                }
+                "--train-ntuple" => {
+                    let engine = GameEngineStores::new();
+                    let mut network = NTupleNetwork::new(ntuple::default_tuples());
+                    ntuple::train(&engine, &mut network, TrainConfig::default());
+                    let filename = &args[2];
+                    network
+                        .save(filename)
+                        .expect("Failed to save n-tuple weights");
+                }
                 _ => {
                     let engine = GameEngineStores::new();
                     search(&engine, 1, 4);
                 }
             }
         }
+        4 if args[1] == "--play" => match (load_strategy(&args[2]), args[3].parse::<u64>()) {
+            (Ok(mut strategy), Ok(move_rate)) => run_ai_with_delay(&mut strategy, move_rate),
+            (Err(err), _) => eprintln!("Invalid strategy file: {}", err),
+            (_, Err(err)) => eprintln!("Invalid time: {}", err),
+        },
         _ => {
             let engine = GameEngineStores::new();
             search(&engine, 1, 4);
@@ -1,9 +1,8 @@
 use crate::ai::expectimax_old::Expectimaxx;
-use crate::ai::strategy::{
-    attributes::Column, attributes::Corner, attributes::Row, ban_rules::BanMove,
-    try_rules::TryMove, Strategy,
-};
-use crate::ai::{AI, AII};
+use crate::ai::strategy;
+use crate::ai::strategy::parse::parse_strategy_file;
+use crate::ai::strategy::Strategy;
+use crate::ai::{Explain, AI, AII};
 use crate::engine::{GameEngineNoStores, Move};
 
 use crate::engine_unsafe as GameEngine;
@@ -41,16 +40,14 @@ pub struct WasmSnake(Strategy);
 #[wasm_bindgen]
 impl WasmSnake {
     pub fn new() -> Self {
-        let ban_rules = vec![BanMove::IfColumnNotLocked(Move::Up, Column::Left)];
-        let try_rules = vec![
-            TryMove::IfMovesLargestTileToCorner(Move::Left, Corner::BottomLeft),
-            TryMove::ProducesMerge(Move::Up),
-            TryMove::ProducesMerge(Move::Down),
-            TryMove::CreatesMonotonicRow(Move::Down, Row::MiddleTop),
-        ];
-        let fallback = vec![Move::Left, Move::Up, Move::Down, Move::Right];
-        let strategy = Strategy::new(&ban_rules, &try_rules, &fallback).unwrap();
-        WasmSnake(strategy)
+        WasmSnake(strategy::snake())
+    }
+
+    // Loads a strategy written in the strategy file format
+    pub fn from_strategy(contents: &str) -> Result<WasmSnake, JsValue> {
+        parse_strategy_file(contents)
+            .map(WasmSnake)
+            .map_err(|err| JsValue::from_str(&err.to_string()))
     }
 
     pub fn get_next_move(&mut self, board: Board) -> i32 {
@@ -64,4 +61,9 @@ impl WasmSnake {
             None => -1,
         }
     }
+
+    // Why the strategy would make its next move, for the user testing site
+    pub fn explain_move(&mut self, board: Board) -> String {
+        self.0.explain(&GameEngineNoStores, board).to_string()
+    }
 }